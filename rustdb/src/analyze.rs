@@ -1,64 +1,845 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OptionalExtension};
 use comfy_table::Table;
+use anyhow::{Context, Result, anyhow, bail};
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
 
-pub fn analyze_table(db_path: &str, table: &str) -> Result<()> {
+use crate::output::value_to_string;
+use crate::sql::{quote_ident, split_qualified_name};
+use crate::stats::{interpolate, quantile_rank};
+
+/// A table or view found in a schema's `sqlite_schema`
+struct TableRef {
+    schema: String,
+    name: String,
+    kind: String,
+}
+
+impl TableRef {
+    /// The quoted `schema.name` form, safe to splice into SQL
+    fn qualified(&self) -> String {
+        format!("{}.{}", quote_ident(&self.schema), quote_ident(&self.name))
+    }
+}
+
+/// Which tables to analyze and where the report goes
+pub struct AnalyzeOptions {
+    /// A single table or view; `None` analyzes everything
+    pub table: Option<String>,
+    pub attach: Vec<(String, String)>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Also list SQLite's own tables, such as `sqlite_sequence` and `sqlite_stat1`
+    pub internal: bool,
+    /// Write the report as JSON to this file, or stdout for `-`
+    pub json: Option<String>,
+    /// How many of the most frequent values to report per column
+    pub top: usize,
+}
+
+#[derive(Serialize)]
+struct DatabaseReport {
+    database: String,
+    tables: Vec<TableReport>,
+}
+
+#[derive(Serialize)]
+struct TableReport {
+    schema: String,
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    columns: Vec<ColumnInfo>,
+    indexes: Vec<IndexInfo>,
+    foreign_keys: Vec<ForeignKey>,
+    triggers: Vec<TriggerInfo>,
+    create_sql: Option<String>,
+    profile: Vec<ColumnProfile>,
+    row_count: i64,
+    /// Bytes used by the table and its indexes; absent for views
+    size_bytes: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct ColumnInfo {
+    name: String,
+    #[serde(rename = "type")]
+    col_type: String,
+    not_null: bool,
+    default: Option<String>,
+    /// Position in the primary key, starting at 1; 0 when not part of it
+    primary_key: i64,
+}
+
+#[derive(Serialize)]
+struct IndexInfo {
+    name: String,
+    unique: bool,
+    /// `c` for CREATE INDEX, `u` for a UNIQUE constraint, `pk` for the primary key
+    origin: String,
+    partial: bool,
+    columns: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ForeignKey {
+    columns: Vec<String>,
+    table: String,
+    /// Referenced columns; empty when the parent's primary key is implied
+    references: Vec<String>,
+    on_update: String,
+    on_delete: String,
+}
+
+#[derive(Serialize)]
+struct TriggerInfo {
+    name: String,
+    sql: String,
+}
+
+/// Data-quality profile of one column
+#[derive(Serialize)]
+struct ColumnProfile {
+    column: String,
+    affinity: Affinity,
+    null_count: i64,
+    null_ratio: f64,
+    distinct_count: i64,
+    top_values: Vec<ValueCount>,
+    /// Lengths of the column's TEXT values; absent when it holds none
+    text_length: Option<LengthStats>,
+    /// Present for columns with numeric affinity, or untyped columns holding only numbers
+    numeric: Option<NumericStats>,
+}
+
+#[derive(Serialize)]
+struct ValueCount {
+    value: String,
+    count: i64,
+}
+
+#[derive(Serialize)]
+struct LengthStats {
+    min: i64,
+    max: i64,
+    avg: f64,
+}
+
+#[derive(Serialize)]
+struct NumericStats {
+    min: Option<f64>,
+    max: Option<f64>,
+    avg: Option<f64>,
+    /// Population standard deviation
+    stddev: Option<f64>,
+    median: Option<f64>,
+    p25: Option<f64>,
+    p75: Option<f64>,
+    p95: Option<f64>,
+    p99: Option<f64>,
+}
+
+/// SQLite column affinity, derived from the declared type
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "UPPERCASE")]
+enum Affinity {
+    Integer,
+    Text,
+    Blob,
+    Real,
+    Numeric,
+}
+
+impl Affinity {
+    /// Applies the rules from section 3.1 of https://www.sqlite.org/datatype3.html, in order
+    fn from_declared(declared: &str) -> Self {
+        let declared = declared.to_uppercase();
+        if declared.contains("INT") {
+            Affinity::Integer
+        } else if declared.contains("CHAR") || declared.contains("CLOB") || declared.contains("TEXT") {
+            Affinity::Text
+        } else if declared.contains("BLOB") || declared.trim().is_empty() {
+            Affinity::Blob
+        } else if declared.contains("REAL") || declared.contains("FLOA") || declared.contains("DOUB") {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    fn is_numeric(self) -> bool {
+        matches!(self, Affinity::Integer | Affinity::Real | Affinity::Numeric)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Affinity::Integer => "INTEGER",
+            Affinity::Text => "TEXT",
+            Affinity::Blob => "BLOB",
+            Affinity::Real => "REAL",
+            Affinity::Numeric => "NUMERIC",
+        }
+    }
+}
+
+/// Analyzes one table or view, or every one in the database
+pub fn analyze(db_path: &str, options: &AnalyzeOptions) -> Result<()> {
     let conn = Connection::open(db_path)?;
+    attach_databases(&conn, &options.attach)?;
+
+    let targets = match &options.table {
+        Some(table) => vec![resolve_table(&conn, table)?],
+        None => list_tables(&conn, &options.include, &options.exclude, options.internal)?,
+    };
+
+    let mut tables = Vec::new();
+    for target in &targets {
+        tables.push(analyze_table(&conn, target, options.top)?);
+    }
+    let report = DatabaseReport { database: db_path.to_string(), tables };
 
+    match options.json.as_deref() {
+        Some("-") => println!("{}", serde_json::to_string_pretty(&report)?),
+        Some(file) => {
+            let out = File::create(file).with_context(|| format!("Failed to create '{}'", file))?;
+            serde_json::to_writer_pretty(out, &report)?;
+            println!("Exported analysis to JSON: {}", file);
+        }
+        None => {
+            let mut out = io::stdout().lock();
+            for table in &report.tables {
+                write_table_report(&mut out, table)?;
+            }
+            if options.table.is_none() {
+                print_summary(&report);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The text report for one table or view, as `analyze <table>` prints it
+pub fn table_report(conn: &Connection, table: &str, top: usize) -> Result<String> {
+    let target = resolve_table(conn, table)?;
+    let report = analyze_table(conn, &target, top)?;
+    let mut out = Vec::new();
+    write_table_report(&mut out, &report)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Runs the schema, profiling and row-count checks for one table or view
+fn analyze_table(conn: &Connection, target: &TableRef, top: usize) -> Result<TableReport> {
     // Get table schema
-    let mut stmt = conn.prepare(&format!("PRAGMA table_info({});", table))?;
-    let columns = stmt.query_map([], |row| {
-        Ok((
-            row.get::<_, String>(1)?,
-            row.get::<_, String>(2)?,
-        ))
+    let mut stmt = conn.prepare("SELECT name, type, \"notnull\", dflt_value, pk FROM pragma_table_info(?1, ?2)")?;
+    let columns = stmt.query_map([&target.name, &target.schema], |row| {
+        Ok(ColumnInfo {
+            name: row.get(0)?,
+            col_type: row.get(1)?,
+            not_null: row.get(2)?,
+            default: row.get(3)?,
+            primary_key: row.get(4)?,
+        })
     })?
-    .collect::<Result<Vec<(String, String)>>>()?;
+    .collect::<rusqlite::Result<Vec<ColumnInfo>>>()?;
 
-    println!("Schema for table '{}':", table);
+    // Get row count
+    let row_count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {}", target.qualified()),
+        [],
+        |r| r.get(0),
+    )?;
+
+    let mut profile = Vec::new();
+    for column in &columns {
+        profile.push(profile_column(conn, target, column, row_count, top)?);
+    }
+
+    let size_bytes = if target.kind == "table" { table_size(conn, target) } else { None };
+
+    Ok(TableReport {
+        schema: target.schema.clone(),
+        name: target.name.clone(),
+        kind: target.kind.clone(),
+        columns,
+        indexes: list_indexes(conn, target)?,
+        foreign_keys: list_foreign_keys(conn, target)?,
+        triggers: list_triggers(conn, target)?,
+        create_sql: create_sql(conn, target)?,
+        profile,
+        row_count,
+        size_bytes,
+    })
+}
+
+/// Indexes on the table, with the columns each one covers
+fn list_indexes(conn: &Connection, target: &TableRef) -> Result<Vec<IndexInfo>> {
+    let mut stmt = conn.prepare("SELECT name, \"unique\", origin, partial FROM pragma_index_list(?1, ?2) ORDER BY seq")?;
+    let indexes = stmt
+        .query_map([&target.name, &target.schema], |row| {
+            Ok(IndexInfo {
+                name: row.get(0)?,
+                unique: row.get(1)?,
+                origin: row.get(2)?,
+                partial: row.get(3)?,
+                columns: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<IndexInfo>>>()?;
+
+    let mut column_stmt = conn.prepare("SELECT name FROM pragma_index_info(?1, ?2) ORDER BY seqno")?;
+    indexes
+        .into_iter()
+        .map(|mut index| {
+            index.columns = column_stmt
+                .query_map([&index.name, &target.schema], |row| {
+                    // Expression indexes have no column name
+                    Ok(row.get::<_, Option<String>>(0)?.unwrap_or_else(|| "<expression>".to_string()))
+                })?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            Ok(index)
+        })
+        .collect()
+}
+
+/// Foreign keys, with multi-column keys grouped together
+fn list_foreign_keys(conn: &Connection, target: &TableRef) -> Result<Vec<ForeignKey>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, \"table\", \"from\", \"to\", on_update, on_delete FROM pragma_foreign_key_list(?1, ?2) ORDER BY id, seq",
+    )?;
+    let mut rows = stmt.query([&target.name, &target.schema])?;
+
+    let mut keys: Vec<(i64, ForeignKey)> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let from: String = row.get(2)?;
+        let to: Option<String> = row.get(3)?;
+        match keys.last_mut() {
+            Some((last_id, key)) if *last_id == id => {
+                key.columns.push(from);
+                key.references.extend(to);
+            }
+            _ => keys.push((
+                id,
+                ForeignKey {
+                    columns: vec![from],
+                    table: row.get(1)?,
+                    references: to.into_iter().collect(),
+                    on_update: row.get(4)?,
+                    on_delete: row.get(5)?,
+                },
+            )),
+        }
+    }
+    Ok(keys.into_iter().map(|(_, key)| key).collect())
+}
+
+fn list_triggers(conn: &Connection, target: &TableRef) -> Result<Vec<TriggerInfo>> {
+    let query = format!(
+        "SELECT name, sql FROM {}.sqlite_schema WHERE type = 'trigger' AND tbl_name = ?1 ORDER BY name",
+        quote_ident(&target.schema)
+    );
+    let mut stmt = conn.prepare(&query)?;
+    let triggers = stmt
+        .query_map([&target.name], |row| Ok(TriggerInfo { name: row.get(0)?, sql: row.get(1)? }))?
+        .collect::<rusqlite::Result<Vec<TriggerInfo>>>()?;
+    Ok(triggers)
+}
+
+/// The original CREATE TABLE or CREATE VIEW statement
+fn create_sql(conn: &Connection, target: &TableRef) -> Result<Option<String>> {
+    let query = format!(
+        "SELECT sql FROM {}.sqlite_schema WHERE type = ?1 AND name = ?2",
+        quote_ident(&target.schema)
+    );
+    let sql = conn
+        .query_row(&query, [&target.kind, &target.name], |row| row.get::<_, Option<String>>(0))
+        .optional()?;
+    Ok(sql.flatten())
+}
+
+/// Null, distinct, frequency and length figures for any column, plus numeric statistics where they apply
+fn profile_column(conn: &Connection, target: &TableRef, column: &ColumnInfo, row_count: i64, top: usize) -> Result<ColumnProfile> {
+    let col = quote_ident(&column.name);
+    let table = target.qualified();
+    let affinity = Affinity::from_declared(&column.col_type);
+
+    let query = format!(
+        "SELECT COUNT(*) - COUNT({0}), COUNT(DISTINCT {0}), \
+         SUM(typeof({0}) NOT IN ('integer', 'real', 'null')), \
+         MIN(LENGTH(CASE WHEN typeof({0}) = 'text' THEN {0} END)), \
+         MAX(LENGTH(CASE WHEN typeof({0}) = 'text' THEN {0} END)), \
+         AVG(LENGTH(CASE WHEN typeof({0}) = 'text' THEN {0} END)) \
+         FROM {1}",
+        col, table
+    );
+    let (null_count, distinct_count, non_numeric, len_min, len_max, len_avg): (i64, i64, Option<i64>, Option<i64>, Option<i64>, Option<f64>) =
+        conn.query_row(&query, [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?)))?;
+
+    let text_length = match (len_min, len_max, len_avg) {
+        (Some(min), Some(max), Some(avg)) => Some(LengthStats { min, max, avg }),
+        _ => None,
+    };
+
+    // Most frequent values
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {0}, COUNT(*) AS n FROM {1} WHERE {0} IS NOT NULL GROUP BY {0} ORDER BY n DESC, {0} LIMIT ?1",
+        col, table
+    ))?;
+    let top_values = stmt
+        .query_map([top as i64], |row| {
+            Ok(ValueCount {
+                value: value_to_string(&row.get_ref(0)?),
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<ValueCount>>>()?;
+
+    // Untyped columns (e.g. computed view columns) count as numeric when every value is a number
+    let non_null = row_count - null_count;
+    let numeric_values = affinity.is_numeric()
+        || (affinity == Affinity::Blob && non_null > 0 && non_numeric.unwrap_or(0) == 0);
+    let numeric = if numeric_values {
+        Some(numeric_stats(conn, &col, &table)?)
+    } else {
+        None
+    };
+
+    Ok(ColumnProfile {
+        column: column.name.clone(),
+        affinity,
+        null_count,
+        null_ratio: if row_count > 0 { null_count as f64 / row_count as f64 } else { 0.0 },
+        distinct_count,
+        top_values,
+        text_length,
+        numeric,
+    })
+}
+
+/// Statistics over the INTEGER and REAL values of a column
+fn numeric_stats(conn: &Connection, col: &str, table: &str) -> Result<NumericStats> {
+    let filter = format!("typeof({0}) IN ('integer', 'real')", col);
+    let query = format!("SELECT COUNT({0}), MIN({0}), MAX({0}), AVG({0}) FROM {1} WHERE {2}", col, table, filter);
+    let (count, min, max, avg): (i64, Option<f64>, Option<f64>, Option<f64>) =
+        conn.query_row(&query, [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)))?;
+
+    // Squared distances from the mean, rather than E[X^2] - E[X]^2, which cancels out for large values
+    let stddev = match avg {
+        Some(avg) => {
+            let query = format!("SELECT AVG(({0} - ?1) * ({0} - ?1)) FROM {1} WHERE {2}", col, table, filter);
+            let variance: Option<f64> = conn.query_row(&query, [avg], |r| r.get(0))?;
+            variance.map(f64::sqrt)
+        }
+        None => None,
+    };
+
+    // Each percentile interpolates linearly between the two closest ranks
+    let quantiles = [0.5, 0.25, 0.75, 0.95, 0.99];
+    let ranks: Vec<f64> = quantiles.iter().map(|&q| quantile_rank(q, count as usize)).collect();
+    let mut wanted: Vec<usize> = ranks.iter().flat_map(|r| [r.floor() as usize, r.ceil() as usize]).collect();
+    wanted.sort_unstable();
+    wanted.dedup();
+
+    // One sorted pass picks up the values at every rank needed
+    let mut values = HashMap::new();
+    if count > 0 {
+        let query = format!("SELECT {0} FROM {1} WHERE {2} ORDER BY {0}", col, table, filter);
+        let mut stmt = conn.prepare(&query)?;
+        let mut rows = stmt.query([])?;
+        let last = wanted.last().copied().unwrap_or(0);
+        let mut index = 0;
+        while index <= last
+            && let Some(row) = rows.next()?
+        {
+            if wanted.binary_search(&index).is_ok() {
+                values.insert(index, row.get::<_, f64>(0)?);
+            }
+            index += 1;
+        }
+    }
+    let percentile = |rank: f64| interpolate(rank, |i| values.get(&i).copied());
+
+    Ok(NumericStats {
+        min,
+        max,
+        avg,
+        stddev,
+        median: percentile(ranks[0]),
+        p25: percentile(ranks[1]),
+        p75: percentile(ranks[2]),
+        p95: percentile(ranks[3]),
+        p99: percentile(ranks[4]),
+    })
+}
+
+/// Writes the text report for one table; the CLI sends it to stdout and the TUI shows it in a pane
+fn write_table_report(out: &mut impl Write, report: &TableReport) -> io::Result<()> {
+    writeln!(out, "Schema for {} '{}.{}':", report.kind, report.schema, report.name)?;
 
     let mut schema_table = Table::new();
-    schema_table.set_header(vec!["Column", "Type"]);
-    for (name, col_type) in &columns {
-        schema_table.add_row(vec![name, col_type]);
+    schema_table.set_header(vec!["Column", "Type", "PK", "Not null", "Default"]);
+    for column in &report.columns {
+        schema_table.add_row(vec![
+            column.name.clone(),
+            column.col_type.clone(),
+            if column.primary_key > 0 { column.primary_key.to_string() } else { String::new() },
+            if column.not_null { "yes".to_string() } else { String::new() },
+            column.default.clone().unwrap_or_default(),
+        ]);
     }
-    println!("{schema_table}");
+    writeln!(out, "{schema_table}")?;
 
-    // Numeric column stats
-    let numeric_columns: Vec<_> = columns
-        .iter()
-        .filter(|(_, t)| t.eq_ignore_ascii_case("INTEGER") || t.eq_ignore_ascii_case("REAL"))
-        .collect();
+    if !report.indexes.is_empty() {
+        writeln!(out, "Indexes:")?;
+        let mut index_table = Table::new();
+        index_table.set_header(vec!["Name", "Columns", "Unique", "Origin", "Partial"]);
+        for index in &report.indexes {
+            index_table.add_row(vec![
+                index.name.clone(),
+                index.columns.join(", "),
+                if index.unique { "yes".to_string() } else { String::new() },
+                index_origin(&index.origin).to_string(),
+                if index.partial { "yes".to_string() } else { String::new() },
+            ]);
+        }
+        writeln!(out, "{index_table}")?;
+    }
 
-    if !numeric_columns.is_empty() {
-        println!("Column statistics:");
-        let mut stats_table = Table::new();
-        stats_table.set_header(vec!["Column", "Min", "Max", "Average"]);
+    if !report.foreign_keys.is_empty() {
+        writeln!(out, "Foreign keys:")?;
+        let mut fk_table = Table::new();
+        fk_table.set_header(vec!["Columns", "References", "On update", "On delete"]);
+        for key in &report.foreign_keys {
+            fk_table.add_row(vec![
+                key.columns.join(", "),
+                format!("{}({})", key.table, key.references.join(", ")),
+                key.on_update.clone(),
+                key.on_delete.clone(),
+            ]);
+        }
+        writeln!(out, "{fk_table}")?;
+    }
 
-        for (name, _) in numeric_columns {
-            let query = format!(
-                "SELECT MIN({0}), MAX({0}), AVG({0}) FROM {1}",
-                name, table
-            );
-            let (min, max, avg): (Option<f64>, Option<f64>, Option<f64>) =
-                conn.query_row(&query, [], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?;
+    if !report.triggers.is_empty() {
+        writeln!(out, "Triggers:")?;
+        for trigger in &report.triggers {
+            writeln!(out, "{}", trigger.sql)?;
+        }
+    }
 
+    if let Some(sql) = &report.create_sql {
+        writeln!(out, "Definition:")?;
+        writeln!(out, "{}", sql)?;
+    }
+
+    if !report.profile.is_empty() {
+        writeln!(out, "Column profile:")?;
+        let mut profile_table = Table::new();
+        profile_table.set_header(vec!["Column", "Affinity", "Nulls", "Null %", "Distinct", "Text length (min/max/avg)"]);
+        for profile in &report.profile {
+            profile_table.add_row(vec![
+                profile.column.clone(),
+                profile.affinity.as_str().to_string(),
+                profile.null_count.to_string(),
+                format!("{:.1}", profile.null_ratio * 100.0),
+                profile.distinct_count.to_string(),
+                profile
+                    .text_length
+                    .as_ref()
+                    .map(|l| format!("{} / {} / {:.1}", l.min, l.max, l.avg))
+                    .unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+        writeln!(out, "{profile_table}")?;
+
+        writeln!(out, "Top values:")?;
+        let mut top_table = Table::new();
+        top_table.set_header(vec!["Column", "Most frequent (count)"]);
+        for profile in &report.profile {
+            let values: Vec<String> = profile
+                .top_values
+                .iter()
+                .map(|v| format!("{} ({})", v.value, v.count))
+                .collect();
+            top_table.add_row(vec![profile.column.clone(), values.join(", ")]);
+        }
+        writeln!(out, "{top_table}")?;
+    }
+
+    let numeric: Vec<(&String, &NumericStats)> = report
+        .profile
+        .iter()
+        .filter_map(|p| p.numeric.as_ref().map(|n| (&p.column, n)))
+        .collect();
+    if !numeric.is_empty() {
+        writeln!(out, "Column statistics:")?;
+        let mut stats_table = Table::new();
+        stats_table.set_header(vec!["Column", "Min", "Max", "Average", "Std dev", "P25", "Median", "P75", "P95", "P99"]);
+        for (column, stats) in numeric {
             stats_table.add_row(vec![
-                name.to_string(),
-                min.map(|v| v.to_string()).unwrap_or("NULL".to_string()),
-                max.map(|v| v.to_string()).unwrap_or("NULL".to_string()),
-                avg.map(|v| v.to_string()).unwrap_or("NULL".to_string()),
+                column.to_string(),
+                format_stat(stats.min),
+                format_stat(stats.max),
+                format_stat(stats.avg),
+                format_stat(stats.stddev),
+                format_stat(stats.p25),
+                format_stat(stats.median),
+                format_stat(stats.p75),
+                format_stat(stats.p95),
+                format_stat(stats.p99),
             ]);
         }
-        println!("{stats_table}");
+        writeln!(out, "{stats_table}")?;
     }
 
-    // Get row count
-    let count: i32 = conn.query_row(
-        &format!("SELECT COUNT (*) FROM {}", table),
-        [],
-        |r| r.get(0),
-    )?;
-    println!("Total rows: {}", count);
+    writeln!(out, "Total rows: {}", report.row_count)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+/// Prints one line per table with its row count and size
+fn print_summary(report: &DatabaseReport) {
+    println!("Summary:");
+    let mut summary = Table::new();
+    summary.set_header(vec!["Name", "Type", "Rows", "Size"]);
+    for table in &report.tables {
+        summary.add_row(vec![
+            format!("{}.{}", table.schema, table.name),
+            table.kind.clone(),
+            table.row_count.to_string(),
+            table.size_bytes.map(format_bytes).unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+    println!("{summary}");
+}
+
+fn index_origin(origin: &str) -> &str {
+    match origin {
+        "c" => "CREATE INDEX",
+        "u" => "UNIQUE constraint",
+        "pk" => "PRIMARY KEY",
+        other => other,
+    }
+}
+
+/// Rounds to four decimal places for display; the JSON report keeps full precision
+fn format_stat(value: Option<f64>) -> String {
+    match value {
+        Some(v) => {
+            let rounded = format!("{:.4}", v);
+            rounded.trim_end_matches('0').trim_end_matches('.').to_string()
+        }
+        None => "NULL".to_string(),
+    }
+}
+
+fn format_bytes(bytes: i64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
 
+/// Bytes used by a table and its indexes, from the `dbstat` virtual table
+fn table_size(conn: &Connection, target: &TableRef) -> Option<i64> {
+    let query = format!(
+        "SELECT SUM(pgsize) FROM dbstat(?1) WHERE name IN \
+         (SELECT name FROM {}.sqlite_schema WHERE tbl_name = ?2 AND type IN ('table', 'index'))",
+        quote_ident(&target.schema)
+    );
+    conn.query_row(&query, [&target.schema, &target.name], |r| r.get::<_, Option<i64>>(0))
+        .ok()
+        .flatten()
+}
+
+/// Every table and view in all attached schemas, filtered by glob patterns; SQLite's own tables only with `internal`
+fn list_tables(conn: &Connection, include: &[String], exclude: &[String], internal: bool) -> Result<Vec<TableRef>> {
+    let include = compile_patterns(include)?;
+    let exclude = compile_patterns(exclude)?;
+
+    let mut tables = Vec::new();
+    for schema in schema_names(conn)? {
+        let query = format!(
+            "SELECT name, type FROM {}.sqlite_schema \
+             WHERE type IN ('table', 'view') AND (?1 OR name NOT LIKE 'sqlite\\_%' ESCAPE '\\') ORDER BY type, name",
+            quote_ident(&schema)
+        );
+        let mut stmt = conn.prepare(&query)?;
+        let found = stmt
+            .query_map([internal], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+
+        for (name, kind) in found {
+            let table = TableRef { schema: schema.clone(), name, kind };
+            let included = include.is_empty() || include.iter().any(|p| matches_table(p, &table));
+            let excluded = exclude.iter().any(|p| matches_table(p, &table));
+            if included && !excluded {
+                tables.push(table);
+            }
+        }
+    }
+    Ok(tables)
+}
+
+fn compile_patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|p| Pattern::new(p).with_context(|| format!("Invalid glob pattern '{}'", p)))
+        .collect()
+}
+
+/// Patterns match the bare table name, or `schema.name` when they contain a dot
+fn matches_table(pattern: &Pattern, table: &TableRef) -> bool {
+    let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+    if pattern.as_str().contains('.') {
+        pattern.matches_with(&format!("{}.{}", table.schema, table.name), options)
+    } else {
+        pattern.matches_with(&table.name, options)
+    }
+}
+
+/// Parses an `--attach name=path` value
+pub fn parse_attach(raw: &str) -> Result<(String, String)> {
+    match raw.split_once('=') {
+        Some((name, path)) if !name.is_empty() && !path.is_empty() => Ok((name.to_string(), path.to_string())),
+        _ => bail!("Expected name=path, got '{}'", raw),
+    }
+}
+
+/// Attaches extra database files under the given schema names
+fn attach_databases(conn: &Connection, attach: &[(String, String)]) -> Result<()> {
+    for (name, path) in attach {
+        conn.execute(&format!("ATTACH DATABASE ?1 AS {}", quote_ident(name)), [path])?;
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Looks a table or view up in `sqlite_schema`, honouring an optional `schema.` prefix.
+///
+/// Unqualified names are searched for in the same order SQLite uses: temp, main, then attached databases.
+fn resolve_table(conn: &Connection, input: &str) -> Result<TableRef> {
+    let schemas = schema_names(conn)?;
+
+    let (schema, name) = split_qualified_name(input);
+    if let Some(prefix) = &schema
+        && let Some(schema) = schemas.iter().find(|s| s.eq_ignore_ascii_case(prefix))
+    {
+        return find_in_schema(conn, schema, &name)?
+            .ok_or_else(|| anyhow!("No table or view named '{}' in schema '{}'", name, schema));
+    }
+
+    // Unqualified, or the prefix is not a database, e.g. a table literally named "a.b"
+    let name = if schema.is_some() { input.trim().to_string() } else { name };
+    for schema in &schemas {
+        if let Some(found) = find_in_schema(conn, schema, &name)? {
+            return Ok(found);
+        }
+    }
+    bail!("No table or view named '{}'", input)
+}
+
+/// Schema names in SQLite's lookup order
+fn schema_names(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_database_list ORDER BY seq")?;
+    let mut names = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    if let Some(pos) = names.iter().position(|n| n == "temp") {
+        let temp = names.remove(pos);
+        names.insert(0, temp);
+    }
+    Ok(names)
+}
+
+fn find_in_schema(conn: &Connection, schema: &str, name: &str) -> Result<Option<TableRef>> {
+    let query = format!(
+        "SELECT name, type FROM {}.sqlite_schema WHERE type IN ('table', 'view') AND name = ?1 COLLATE NOCASE",
+        quote_ident(schema)
+    );
+    let found = conn
+        .query_row(&query, [name], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .optional()?;
+    Ok(found.map(|(name, kind)| TableRef { schema: schema.to_string(), name, kind }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn affinity_follows_sqlites_rules() {
+        let cases = [
+            ("INTEGER", Affinity::Integer),
+            ("BIGINT", Affinity::Integer),
+            ("unsigned big int", Affinity::Integer),
+            // INT wins over every later rule
+            ("FLOATING POINT", Affinity::Integer),
+            ("VARCHAR(255)", Affinity::Text),
+            ("NCHAR", Affinity::Text),
+            ("CLOB", Affinity::Text),
+            ("text", Affinity::Text),
+            ("BLOB", Affinity::Blob),
+            ("", Affinity::Blob),
+            ("  ", Affinity::Blob),
+            ("REAL", Affinity::Real),
+            ("FLOAT", Affinity::Real),
+            ("DOUBLE PRECISION", Affinity::Real),
+            ("NUMERIC", Affinity::Numeric),
+            ("DECIMAL(10,2)", Affinity::Numeric),
+            ("BOOLEAN", Affinity::Numeric),
+            ("DATETIME", Affinity::Numeric),
+            ("STRING", Affinity::Numeric),
+        ];
+        for (declared, affinity) in cases {
+            assert_eq!(Affinity::from_declared(declared), affinity, "{}", declared);
+        }
+    }
+
+    fn stats_for(values: &str) -> NumericStats {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(&format!("CREATE TABLE t (x); INSERT INTO t VALUES {};", values)).unwrap();
+        numeric_stats(&conn, "\"x\"", "\"t\"").unwrap()
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_values() {
+        // Text and NULL values are left out
+        let stats = stats_for("(4), (1), (3), (2), ('x'), (NULL)");
+        assert_eq!((stats.min, stats.max, stats.avg), (Some(1.0), Some(4.0), Some(2.5)));
+        assert_eq!(stats.median, Some(2.5));
+        assert_eq!(stats.p25, Some(1.75));
+        assert_eq!(stats.p75, Some(3.25));
+        assert!((stats.p95.unwrap() - 3.85).abs() < 1e-9);
+        assert!((stats.p99.unwrap() - 3.97).abs() < 1e-9);
+        assert!((stats.stddev.unwrap() - 1.25f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn single_and_missing_values() {
+        let stats = stats_for("(7.5)");
+        assert_eq!((stats.median, stats.p99, stats.stddev), (Some(7.5), Some(7.5), Some(0.0)));
+        let stats = stats_for("('a'), (NULL)");
+        assert_eq!((stats.min, stats.median, stats.stddev), (None, None, None));
+    }
+
+    #[test]
+    fn stddev_keeps_its_precision_for_large_values() {
+        // E[X^2] - E[X]^2 loses every digit here; the spread is 1
+        let base = 1_000_000_000_i64;
+        let stats = stats_for(&format!("({}), ({}), ({})", base - 1, base, base + 1));
+        assert!((stats.stddev.unwrap() - (2.0f64 / 3.0).sqrt()).abs() < 1e-6, "{:?}", stats.stddev);
+        assert_eq!(stats.median, Some(base as f64));
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Result, bail};
+use comfy_table::Table;
+use rusqlite::Connection;
+use std::time::Instant;
+
+use crate::output::write_rows;
+use crate::params::{ParamBinder, QueryParams};
+use crate::script::{leading_keyword, split_statements};
+use crate::stats::percentile;
+
+/// Options for `bench`
+pub struct BenchOptions {
+    /// Timed runs
+    pub runs: usize,
+    /// Untimed runs before the timed ones
+    pub warmup: usize,
+    /// Also time runs that each open a new connection
+    pub cold: bool,
+    pub params: QueryParams,
+}
+
+/// Run times in milliseconds, summarised
+struct Summary {
+    min: f64,
+    max: f64,
+    mean: f64,
+    median: f64,
+    p95: f64,
+    p99: f64,
+    stddev: f64,
+}
+
+impl Summary {
+    fn new(mut times: Vec<f64>) -> Self {
+        times.sort_by(f64::total_cmp);
+        let n = times.len();
+        let mean = times.iter().sum::<f64>() / n as f64;
+        // Sample standard deviation; a single run has none
+        let variance = if n > 1 { times.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / (n - 1) as f64 } else { 0.0 };
+        Summary {
+            min: times[0],
+            max: times[n - 1],
+            mean,
+            median: percentile(&times, 0.5).unwrap_or(mean),
+            p95: percentile(&times, 0.95).unwrap_or(mean),
+            p99: percentile(&times, 0.99).unwrap_or(mean),
+            stddev: variance.sqrt(),
+        }
+    }
+
+    fn rows(&self) -> [(&'static str, f64); 7] {
+        [
+            ("min", self.min),
+            ("max", self.max),
+            ("mean", self.mean),
+            ("median", self.median),
+            ("p95", self.p95),
+            ("p99", self.p99),
+            ("stddev", self.stddev),
+        ]
+    }
+}
+
+/// Runs SQL repeatedly and prints statistics of how long it took.
+///
+/// Only preparing, executing and fetching every row are timed; nothing is printed per run.
+/// Warm runs share one connection, so SQLite's page cache fills up; cold runs each open a new
+/// one. Statements that change data run in a transaction that is rolled back after each run.
+pub fn bench(db_path: &str, sql: &str, options: &BenchOptions) -> Result<()> {
+    let statements: Vec<String> = split_statements(sql).into_iter().map(|s| s.sql).collect();
+    if statements.is_empty() {
+        bail!("No SQL statements to run");
+    }
+    // Runs that change data are wrapped in a transaction of their own, which these would end or nest in
+    for (n, statement) in statements.iter().enumerate() {
+        let keyword = leading_keyword(statement);
+        if matches!(keyword.as_str(), "BEGIN" | "COMMIT" | "END" | "ROLLBACK" | "SAVEPOINT" | "RELEASE") {
+            bail!(
+                "Statement {} is a {} statement; bench runs the SQL in a transaction it rolls back, so leave out transaction control",
+                n + 1,
+                keyword
+            );
+        }
+    }
+
+    // A first run, rolled back, catches mistakes before any timing and finds out whether the SQL writes
+    let conn = Connection::open(db_path)?;
+    let mut read_only = true;
+    let mut binder = ParamBinder::new(&options.params, statements.len());
+    conn.execute_batch("BEGIN")?;
+    for (n, statement) in statements.iter().enumerate() {
+        let checked = conn.prepare(statement).map_err(anyhow::Error::from).and_then(|stmt| {
+            read_only &= stmt.readonly();
+            run_statement(&conn, statement, &mut binder)
+        });
+        if let Err(e) = checked {
+            conn.execute_batch("ROLLBACK")?;
+            return Err(e.context(format!("Statement {} failed", n + 1)));
+        }
+    }
+    conn.execute_batch("ROLLBACK")?;
+    binder.finish()?;
+
+    let run = |conn: &Connection| -> Result<(f64, usize)> {
+        if !read_only {
+            conn.execute_batch("BEGIN")?;
+        }
+        let started = Instant::now();
+        let rows = run_statements(conn, &statements, &options.params);
+        let elapsed = started.elapsed().as_secs_f64() * 1000.0;
+        if !read_only {
+            conn.execute_batch("ROLLBACK")?;
+        }
+        Ok((elapsed, rows?))
+    };
+
+    for _ in 0..options.warmup {
+        run(&conn)?;
+    }
+    let mut warm = Vec::with_capacity(options.runs);
+    let mut rows = 0;
+    for _ in 0..options.runs {
+        let (elapsed, count) = run(&conn)?;
+        warm.push(elapsed);
+        rows = count;
+    }
+    drop(conn);
+
+    let mut cold = Vec::new();
+    if options.cold {
+        for _ in 0..options.runs {
+            cold.push(run(&Connection::open(db_path)?)?.0);
+        }
+    }
+
+    println!(
+        "{} timed run(s) after {} warm-up run(s), {} row(s) fetched per run{}",
+        options.runs,
+        options.warmup,
+        rows,
+        if read_only { "" } else { ", changes rolled back" }
+    );
+    let mut table = Table::new();
+    let warm = Summary::new(warm);
+    if cold.is_empty() {
+        table.set_header(vec!["", "ms"]);
+        for (label, value) in warm.rows() {
+            table.add_row(vec![label.to_string(), format!("{:.3}", value)]);
+        }
+    } else {
+        let cold = Summary::new(cold);
+        table.set_header(vec!["", "warm ms", "cold ms"]);
+        for ((label, warm), (_, cold)) in warm.rows().into_iter().zip(cold.rows()) {
+            table.add_row(vec![label.to_string(), format!("{:.3}", warm), format!("{:.3}", cold)]);
+        }
+    }
+    println!("{table}");
+    Ok(())
+}
+
+/// Runs every statement, reading every value of every row; returns how many rows were fetched
+fn run_statements(conn: &Connection, statements: &[String], params: &QueryParams) -> Result<usize> {
+    let mut binder = ParamBinder::new(params, statements.len());
+    let mut rows = 0;
+    for sql in statements {
+        rows += run_statement(conn, sql, &mut binder)?;
+    }
+    Ok(rows)
+}
+
+fn run_statement(conn: &Connection, sql: &str, binder: &mut ParamBinder) -> Result<usize> {
+    let mut stmt = conn.prepare(sql)?;
+    binder.bind(&mut stmt)?;
+    if stmt.column_count() == 0 {
+        stmt.raw_execute()?;
+        return Ok(0);
+    }
+    write_rows(&mut stmt, &mut [])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarises_a_single_run() {
+        let summary = Summary::new(vec![4.0]);
+        assert!(summary.rows().iter().all(|(label, value)| *value == if *label == "stddev" { 0.0 } else { 4.0 }));
+    }
+
+    #[test]
+    fn summarises_an_even_number_of_runs() {
+        let summary = Summary::new(vec![4.0, 1.0, 3.0, 2.0]);
+        assert_eq!((summary.min, summary.max, summary.mean, summary.median), (1.0, 4.0, 2.5, 2.5));
+        // Sample standard deviation: sqrt((2.25 + 0.25 + 0.25 + 2.25) / 3)
+        assert!((summary.stddev - (5.0f64 / 3.0).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn interpolates_high_percentiles() {
+        // 1..=100: ranks 94.05 and 98.01 of 0..=99
+        let summary = Summary::new((1..=100).rev().map(f64::from).collect());
+        assert!((summary.p95 - 95.05).abs() < 1e-9, "{}", summary.p95);
+        assert!((summary.p99 - 99.01).abs() < 1e-9, "{}", summary.p99);
+        let summary = Summary::new(vec![10.0, 20.0]);
+        assert!((summary.p95 - 19.5).abs() < 1e-9);
+        assert!((summary.p99 - 19.9).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,326 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use crate::params::parse_param_spec;
+use crate::saved_queries::{
+    OnConflict, SavedQuery, SavedStores, Scope, declare_params, parse_tags, saved_file,
+};
+
+/// How a collection of saved queries is written for sharing
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum BundleFormat {
+    /// One JSON file in the same format as the saved query stores
+    Json,
+    /// A directory with one `.sql` file per query, its details in a header comment
+    Sql,
+}
+
+/// Writes saved queries to a bundle: the named ones, or all of them (optionally only those with a tag)
+pub fn export_bundle(
+    stores: &SavedStores,
+    scope: Option<Scope>,
+    names: &[String],
+    tag: Option<&str>,
+    path: &str,
+    format: Option<BundleFormat>,
+    force: bool,
+) -> Result<()> {
+    let queries: Vec<SavedQuery> = if names.is_empty() {
+        stores
+            .load()?
+            .into_iter()
+            // Without a scope, a shadowed query is left out in favour of the one that is used
+            .filter(|q| scope.map_or(!q.shadowed, |scope| q.scope == scope))
+            .map(|q| q.query)
+            .filter(|q| tag.is_none_or(|tag| q.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+            .collect()
+    } else {
+        names.iter().map(|name| Ok(stores.find(name, scope)?.query)).collect::<Result<_>>()?
+    };
+    if queries.is_empty() {
+        bail!("No saved queries to export");
+    }
+
+    let is_json = Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    match format.unwrap_or(if is_json { BundleFormat::Json } else { BundleFormat::Sql }) {
+        BundleFormat::Json => saved_file(Path::new(path)).export(&queries)?,
+        BundleFormat::Sql => write_sql_dir(Path::new(path), &queries, force)?,
+    }
+    println!("Exported {} saved quer{} to '{}'", queries.len(), if queries.len() == 1 { "y" } else { "ies" }, path);
+    Ok(())
+}
+
+/// Adds the queries in a bundle to a store, dealing with names already taken as `on_conflict` says
+pub fn import_bundle(
+    stores: &SavedStores,
+    scope: Option<Scope>,
+    path: &str,
+    format: Option<BundleFormat>,
+    on_conflict: OnConflict,
+) -> Result<()> {
+    let path = Path::new(path);
+    if !path.exists() {
+        bail!("No such file or directory: '{}'", path.display());
+    }
+    let is_sql = path.is_dir() || path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sql"));
+    let queries = match format.unwrap_or(if is_sql { BundleFormat::Sql } else { BundleFormat::Json }) {
+        BundleFormat::Json => saved_file(path).load()?,
+        BundleFormat::Sql => read_sql_files(path)?,
+    };
+    // Bundles are edited by hand, so check them as `saved add` would
+    let queries = queries
+        .into_iter()
+        .map(|mut query| {
+            query.name = query.name.trim().to_string();
+            if query.name.is_empty() {
+                bail!("A query in the bundle has no name");
+            }
+            query.params = declare_params(&query.sql, &query.params).with_context(|| format!("In '{}'", query.name))?;
+            Ok(query)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (scope, report) = stores.import(queries, scope, on_conflict)?;
+    println!("Imported into the {} saved queries:", scope.name());
+    println!("  {} added", report.added.len());
+    if !report.overwritten.is_empty() {
+        println!("  {} overwritten: {}", report.overwritten.len(), report.overwritten.join(", "));
+    }
+    for (from, to) in &report.renamed {
+        println!("  '{}' renamed to '{}'", from, to);
+    }
+    if !report.skipped.is_empty() {
+        println!("  {} skipped as the name is taken: {}", report.skipped.len(), report.skipped.join(", "));
+    }
+    Ok(())
+}
+
+/// Writes one `<name>.sql` file per query, replacing files of the same name.
+///
+/// Other `.sql` files already in the directory would be imported along with the export, so they
+/// are an error, or removed with `force`.
+fn write_sql_dir(dir: &Path, queries: &[SavedQuery], force: bool) -> Result<()> {
+    let mut used = HashSet::new();
+    let files: Vec<(String, &SavedQuery)> = queries
+        .iter()
+        .map(|query| {
+            // Names that only differ in characters left out of file names get a number
+            let stem = file_stem(&query.name);
+            let stem = (1..)
+                .map(|n| if n == 1 { stem.clone() } else { format!("{}-{}", stem, n) })
+                .find(|stem| used.insert(stem.to_lowercase()))
+                .unwrap_or_default();
+            (format!("{}.sql", stem), query)
+        })
+        .collect();
+
+    let mut stale = Vec::new();
+    if dir.is_dir() {
+        for entry in fs::read_dir(dir).with_context(|| format!("Failed to read '{}'", dir.display()))? {
+            let file = entry?.path();
+            let name = file.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            let is_sql = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sql"));
+            if is_sql && !files.iter().any(|(written, _)| *written == name) {
+                stale.push(file);
+            }
+        }
+        stale.sort();
+    }
+    if !stale.is_empty() && !force {
+        let names: Vec<String> = stale.iter().filter_map(|f| f.file_name()).map(|f| f.to_string_lossy().to_string()).collect();
+        bail!(
+            "'{}' has .sql files that are not part of this export: {} (pass --force to remove them)",
+            dir.display(),
+            names.join(", ")
+        );
+    }
+    for file in &stale {
+        fs::remove_file(file).with_context(|| format!("Failed to remove '{}'", file.display()))?;
+        println!("Removed '{}'", file.display());
+    }
+
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+    for (name, query) in files {
+        let path = dir.join(name);
+        fs::write(&path, to_sql_file(query)).with_context(|| format!("Failed to write '{}'", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The `.sql` files in a directory, in file name order, or a single `.sql` file
+fn read_sql_files(path: &Path) -> Result<Vec<SavedQuery>> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        for entry in fs::read_dir(path).with_context(|| format!("Failed to read '{}'", path.display()))? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("sql")) {
+                files.push(file);
+            }
+        }
+        files.sort();
+    } else {
+        files.push(path.to_path_buf());
+    }
+    files
+        .iter()
+        .map(|file| {
+            let contents = fs::read_to_string(file).with_context(|| format!("Failed to read '{}'", file.display()))?;
+            let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            parse_sql_file(&contents, &stem).with_context(|| format!("Invalid saved query file '{}'", file.display()))
+        })
+        .collect()
+}
+
+/// The query's SQL after a header of `-- key: value` comments
+fn to_sql_file(query: &SavedQuery) -> String {
+    let mut header = vec![format!("-- name: {}", query.name)];
+    if let Some(description) = &query.description {
+        header.push(format!("-- description: {}", description.replace(['\r', '\n'], " ")));
+    }
+    if !query.tags.is_empty() {
+        header.push(format!("-- tags: {}", query.tags.join(", ")));
+    }
+    header.extend(query.params.iter().map(|param| format!("-- param: {}", param)));
+    if let Some(created) = &query.created {
+        header.push(format!("-- created: {}", created));
+    }
+    if let Some(updated) = &query.updated {
+        header.push(format!("-- updated: {}", updated));
+    }
+    format!("{}\n\n{}\n", header.join("\n"), query.sql.trim_end())
+}
+
+/// Reads a file written by `to_sql_file`; without a `name` line the file name is used.
+///
+/// The header ends at the first line that is not a `-- key: value` comment with a known key, so
+/// ordinary comments at the top of the SQL are kept.
+fn parse_sql_file(contents: &str, stem: &str) -> Result<SavedQuery> {
+    let mut query = SavedQuery::new(stem, "");
+    let mut declared = Vec::new();
+    let mut lines = contents.lines().peekable();
+    while let Some(line) = lines.peek() {
+        let Some((key, value)) = line.strip_prefix("--").and_then(|rest| rest.split_once(':')) else {
+            break;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "name" => query.name = value.to_string(),
+            "description" if !value.is_empty() => query.description = Some(value.to_string()),
+            "description" => {}
+            "tags" => query.tags = parse_tags(value),
+            "param" => declared.push(parse_param_spec(value)?),
+            "created" => query.created = Some(value.to_string()),
+            "updated" => query.updated = Some(value.to_string()),
+            _ => break,
+        }
+        lines.next();
+    }
+    query.sql = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+    if query.sql.is_empty() {
+        bail!("No SQL after the header");
+    }
+    query.params = declared;
+    Ok(query)
+}
+
+/// A file name for a query: letters, digits, `-` and `_`, with anything else as `_`
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if stem.is_empty() { "query".to_string() } else { stem }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::ParamType;
+
+    #[test]
+    fn reads_the_header() {
+        let contents = "-- name: Recent orders\n-- description: Orders since a date\n-- tags: sales, daily\n-- param: since:date=2024-01-01\n-- created: 2024-01-01T00:00:00Z\n\nSELECT * FROM orders\nWHERE created >= :since\n";
+        let query = parse_sql_file(contents, "file").unwrap();
+        assert_eq!(query.name, "Recent orders");
+        assert_eq!(query.description.as_deref(), Some("Orders since a date"));
+        assert_eq!(query.tags, ["sales", "daily"]);
+        assert_eq!(query.params.len(), 1);
+        assert_eq!((query.params[0].name.as_str(), query.params[0].kind), ("since", ParamType::Date));
+        assert_eq!(query.created.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(query.sql, "SELECT * FROM orders\nWHERE created >= :since");
+    }
+
+    #[test]
+    fn uses_the_file_name_without_a_name_line() {
+        let query = parse_sql_file("SELECT 1", "daily_report").unwrap();
+        assert_eq!(query.name, "daily_report");
+        assert_eq!(query.sql, "SELECT 1");
+    }
+
+    #[test]
+    fn keeps_ordinary_comments_as_sql() {
+        // Unknown keys and comments without a colon end the header
+        let query = parse_sql_file("-- name: q\n-- note: keep me\nSELECT 1", "f").unwrap();
+        assert_eq!(query.sql, "-- note: keep me\nSELECT 1");
+        let query = parse_sql_file("-- totals per day\n-- name: q\nSELECT 1", "f").unwrap();
+        assert_eq!(query.name, "f");
+        assert_eq!(query.sql, "-- totals per day\n-- name: q\nSELECT 1");
+    }
+
+    #[test]
+    fn an_empty_description_is_none() {
+        let query = parse_sql_file("-- description:\nSELECT 1", "f").unwrap();
+        assert_eq!(query.description, None);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert!(parse_sql_file("-- name: q\n\n", "f").is_err());
+        assert!(parse_sql_file("-- param: n:number\nSELECT :n", "f").is_err());
+        assert!(parse_sql_file("-- param: =1\nSELECT 1", "f").is_err());
+    }
+
+    #[test]
+    fn written_files_read_back() {
+        let mut query = SavedQuery::new("Top users", "SELECT *\nFROM users WHERE id = :id");
+        query.description = Some("Two\nlines".into());
+        query.tags = vec!["a".into(), "b".into()];
+        query.params = vec![parse_param_spec("id:integer=1").unwrap()];
+        let read = parse_sql_file(&to_sql_file(&query), "x").unwrap();
+        assert_eq!(read.name, query.name);
+        assert_eq!(read.description.as_deref(), Some("Two lines"));
+        assert_eq!(read.tags, query.tags);
+        assert_eq!(read.params, query.params);
+        assert_eq!(read.sql, query.sql);
+    }
+
+    #[test]
+    fn file_stems_are_safe() {
+        assert_eq!(file_stem("Sales / day"), "Sales___day");
+        assert_eq!(file_stem("  "), "query");
+        assert_eq!(file_stem("top-10_users"), "top-10_users");
+    }
+
+    #[test]
+    fn exports_leave_no_stale_files() {
+        let dir = std::env::temp_dir().join(format!("rustdb-bundle-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let queries = [SavedQuery::new("a", "SELECT 1"), SavedQuery::new("b", "SELECT 2")];
+        write_sql_dir(&dir, &queries, false).unwrap();
+        fs::write(dir.join("notes.txt"), "kept").unwrap();
+
+        // Rewriting the same queries is fine; dropping one needs --force
+        write_sql_dir(&dir, &queries, false).unwrap();
+        assert!(write_sql_dir(&dir, &queries[..1], false).is_err());
+        assert!(dir.join("b.sql").exists());
+        write_sql_dir(&dir, &queries[..1], true).unwrap();
+        let read: Vec<String> = read_sql_files(&dir).unwrap().into_iter().map(|q| q.name).collect();
+        assert_eq!(read, ["a"]);
+        assert!(dir.join("notes.txt").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,4 +1,13 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+
+use rusqlite::types::Value;
+
+use crate::analyze::parse_attach;
+use crate::bundle::BundleFormat;
+use crate::import::{HeaderMode, ImportFormat};
+use crate::output::{BlobEncoding, OutputFormat};
+use crate::params::{ParamSpec, parse_named, parse_param_spec, parse_setting, parse_value};
+use crate::saved_queries::{OnConflict, Scope};
 
 /// CLI Interface
 #[derive(Parser, Debug)]
@@ -6,6 +15,10 @@ use clap::{Parser, Subcommand};
 #[command(version)]
 #[command(about = "A lightweight command-line SQLite querty and analysis tool")]
 pub struct Cli {
+    /// Saved queries file to use instead of the database, project and global ones (or $RUSTDB_SAVED_QUERIES)
+    #[clap(long, global = true)]
+    pub saved_queries: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -17,24 +30,69 @@ pub enum Commands {
         /// Path to database file
         db_path: String,
 
-        /// SQL query to run
+        /// SQL query to run (may contain several statements)
+        #[clap(required_unless_present_any = ["file", "saved"])]
+        sql: Option<String>,
+
+        /// Read SQL statements from a file, or '-' for stdin
+        #[clap(long, conflicts_with = "sql")]
+        file: Option<String>,
+
+        /// Run the saved query with this name
+        #[clap(long, conflicts_with_all = ["sql", "file"])]
+        saved: Option<String>,
+
+        /// Value for a parameter of the saved query, as name=value (repeatable; needs --saved)
+        #[clap(long = "set", value_parser = parse_setting)]
+        settings: Vec<(String, String)>,
+
+        #[command(flatten)]
+        options: QueryArgs,
+    },
+
+    /// Run a saved query by name
+    Run {
+        /// Path to database file
+        db_path: String,
+
+        /// Name of the saved query
+        name: String,
+
+        /// Value for a parameter of the saved query, as name=value (repeatable)
+        #[clap(long = "set", value_parser = parse_setting)]
+        settings: Vec<(String, String)>,
+
+        #[command(flatten)]
+        options: QueryArgs,
+    },
+
+    /// Time an SQL query over repeated runs
+    Bench {
+        /// Path to database file
+        db_path: String,
+
+        /// SQL query to time (may contain several statements)
         sql: String,
 
-        /// Optional CSV output
-        #[clap(long)]
-        csv: Option<String>,
+        /// Number of timed runs
+        #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        runs: u32,
 
-        /// Optional JSON output
-        #[clap(long)]
-        json: Option<String>,
+        /// Untimed runs before the timed ones
+        #[clap(long, default_value_t = 1)]
+        warmup: u32,
 
-        /// Optional explain flag
+        /// Also time runs that each open a new connection, starting with an empty SQLite page cache
         #[clap(long)]
-        explain: bool,
+        cold: bool,
 
-        /// Optional profile flag
-        #[clap(long)]
-        profile: bool,
+        /// Positional parameter bound to ?1, ?2, ... (repeatable)
+        #[clap(long = "param", value_parser = parse_value)]
+        params: Vec<Value>,
+
+        /// Named parameter bound to :name, @name or $name (repeatable)
+        #[clap(long = "named", value_parser = parse_named)]
+        named: Vec<(String, Value)>,
     },
 
     /// Analyze a database table (schema, row count, etc.)
@@ -42,12 +100,278 @@ pub enum Commands {
         /// Path to database file
         db_path: String,
 
-        /// Table name to analyze
+        /// Table or view to analyze, optionally schema-qualified (e.g. aux.users); omit to analyze all
+        table: Option<String>,
+
+        /// Also analyze SQLite's internal tables, such as sqlite_sequence and sqlite_stat1
+        #[clap(long, conflicts_with = "table")]
+        all: bool,
+
+        /// Only analyze tables matching this glob (repeatable)
+        #[clap(long, conflicts_with = "table")]
+        include: Vec<String>,
+
+        /// Skip tables matching this glob (repeatable)
+        #[clap(long, conflicts_with = "table")]
+        exclude: Vec<String>,
+
+        /// Write the report as JSON to a file, or '-' for stdout
+        #[clap(long)]
+        json: Option<String>,
+
+        /// Number of most frequent values to report per column
+        #[clap(long, default_value_t = 5)]
+        top: usize,
+
+        /// Attach another database file under a schema name (name=path, repeatable)
+        #[clap(long, value_parser = parse_attach)]
+        attach: Vec<(String, String)>,
+    },
+
+    /// Import a CSV, TSV, JSON or NDJSON file into a table
+    Import {
+        /// Path to database file
+        db_path: String,
+
+        /// File to import, or '-' for stdin
+        file: String,
+
+        /// Table to insert into; created if it does not exist
+        #[clap(long)]
         table: String,
+
+        /// Input format (detected from the file extension by default)
+        #[clap(long, value_enum)]
+        format: Option<ImportFormat>,
+
+        /// Whether the first CSV row holds column names
+        #[clap(long, value_enum, default_value_t = HeaderMode::Auto)]
+        header: HeaderMode,
+
+        /// CSV field delimiter (defaults to ',' or a tab for TSV)
+        #[clap(long)]
+        delimiter: Option<char>,
+
+        /// CSV quote character
+        #[clap(long, default_value_t = '"')]
+        quote: char,
+
+        /// Treat quote characters in CSV input as ordinary text
+        #[clap(long)]
+        no_quoting: bool,
     },
 
     /// Starts a Tui window
     Tui {
         db_path: String,
-    }
-}
\ No newline at end of file
+    },
+
+    /// Show statements run in the TUI against a database
+    History {
+        /// Path to database file
+        db_path: String,
+
+        /// Number of most recent entries to show
+        #[clap(long, default_value_t = 20)]
+        limit: usize,
+
+        /// Only show statements containing this text (case-insensitive)
+        #[clap(long)]
+        search: Option<String>,
+
+        /// Only show statements that failed
+        #[clap(long)]
+        failed: bool,
+
+        /// Print the entries as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Delete the history for this database
+        #[clap(long, conflicts_with_all = ["search", "failed", "json"])]
+        clear: bool,
+    },
+
+    /// List and manage saved queries
+    Saved {
+        /// Database whose own saved queries to include
+        #[clap(long, global = true)]
+        db: Option<String>,
+
+        /// Only use this store: database, project or global (new queries go to the project store when
+        /// there is one, else the global one)
+        #[clap(long, value_enum, global = true)]
+        scope: Option<Scope>,
+
+        #[command(subcommand)]
+        command: SavedCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SavedCommand {
+    /// List saved queries
+    List {
+        /// Only list queries with this tag
+        #[clap(long)]
+        tag: Option<String>,
+
+        /// Print the queries as JSON
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Show a saved query with its description, tags, parameters and timestamps
+    Show {
+        name: String,
+    },
+
+    /// Save a query under a new name
+    Add {
+        name: String,
+
+        /// SQL text of the query
+        sql: String,
+
+        #[clap(long)]
+        description: Option<String>,
+
+        /// Tag for the query (repeatable or comma-separated)
+        #[clap(long = "tag")]
+        tags: Vec<String>,
+
+        /// Declare a parameter as name:type=default, e.g. start:date=2024-01-01 (repeatable)
+        #[clap(long = "declare", value_parser = parse_param_spec)]
+        params: Vec<ParamSpec>,
+
+        /// Replace a saved query that already has this name
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Change the SQL, description, tags or parameters of a saved query
+    Update {
+        name: String,
+
+        /// New SQL text
+        #[clap(long)]
+        sql: Option<String>,
+
+        /// New description; an empty one removes it
+        #[clap(long)]
+        description: Option<String>,
+
+        /// Replace the tags with these (repeatable or comma-separated)
+        #[clap(long = "tag")]
+        tags: Vec<String>,
+
+        /// Remove all tags
+        #[clap(long, conflicts_with = "tags")]
+        clear_tags: bool,
+
+        /// Declare or redeclare a parameter as name:type=default (repeatable)
+        #[clap(long = "declare", value_parser = parse_param_spec)]
+        params: Vec<ParamSpec>,
+    },
+
+    /// Rename a saved query
+    Rename {
+        name: String,
+        new_name: String,
+    },
+
+    /// Delete a saved query
+    Delete {
+        name: String,
+    },
+
+    /// Write saved queries to a JSON bundle or a directory of .sql files, for sharing
+    Export {
+        /// A .json file, or a directory for .sql files
+        path: String,
+
+        /// Queries to export (default: all of them)
+        names: Vec<String>,
+
+        /// Only export queries with this tag
+        #[clap(long, conflicts_with = "names")]
+        tag: Option<String>,
+
+        /// Bundle format (json for a .json path, otherwise sql)
+        #[clap(long, value_enum)]
+        format: Option<BundleFormat>,
+
+        /// Remove .sql files in the directory that are not part of this export
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Add the queries from a JSON bundle, a directory of .sql files or a single .sql file
+    Import {
+        path: String,
+
+        /// What to do with a query whose name is already taken
+        #[clap(long, value_enum, default_value_t = OnConflict::Skip)]
+        on_conflict: OnConflict,
+
+        /// Bundle format (sql for a directory or .sql file, otherwise json)
+        #[clap(long, value_enum)]
+        format: Option<BundleFormat>,
+    },
+}
+
+/// Options shared by `query` and `run`
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// Stop at the first failing statement (default)
+    #[clap(long, conflicts_with = "continue_on_error")]
+    pub bail: bool,
+
+    /// Keep running the remaining statements after one fails
+    #[clap(long)]
+    pub continue_on_error: bool,
+
+    /// Positional parameter bound to ?1, ?2, ... (repeatable; NULL, numbers, text or @file for a blob)
+    #[clap(long = "param", value_parser = parse_value)]
+    pub params: Vec<Value>,
+
+    /// Named parameter bound to :name, @name or $name (repeatable)
+    #[clap(long = "named", value_parser = parse_named)]
+    pub named: Vec<(String, Value)>,
+
+    /// Optional CSV output
+    #[clap(long)]
+    pub csv: Option<String>,
+
+    /// Optional JSON output
+    #[clap(long)]
+    pub json: Option<String>,
+
+    /// Encoding for BLOB values in JSON output
+    #[clap(long, value_enum, default_value_t = BlobEncoding::Base64)]
+    pub blob_encoding: BlobEncoding,
+
+    /// Output format; everything except table is written row by row as it is read
+    #[clap(long, value_enum, default_value_t = OutputFormat::Table)]
+    pub format: OutputFormat,
+
+    /// Write results to a file instead of stdout
+    #[clap(long, short)]
+    pub output: Option<String>,
+
+    /// Maximum number of rows buffered for the table view
+    #[clap(long, default_value_t = 10_000)]
+    pub max_rows: usize,
+
+    /// Table name used by the insert format
+    #[clap(long, default_value = "result")]
+    pub table_name: String,
+
+    /// Optional explain flag
+    #[clap(long)]
+    pub explain: bool,
+
+    /// Optional profile flag
+    #[clap(long)]
+    pub profile: bool,
+}
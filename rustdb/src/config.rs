@@ -0,0 +1,59 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable naming a config file to use instead of the default one
+pub const CONFIG_ENV: &str = "RUSTDB_CONFIG";
+
+/// User settings read from `config.json`; every field is optional
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    /// Key bindings by action name, replacing that action's default keys
+    pub keymap: BTreeMap<String, KeyList>,
+}
+
+/// One key (`"ctrl+q"`) or several (`["enter", "ctrl+enter"]`)
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    pub fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Directory for rustdb's own files, e.g. `~/.config/rustdb` on Linux
+pub fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("rustdb"))
+}
+
+/// The config file: `$RUSTDB_CONFIG` if set, otherwise `config.json` in the config directory
+pub fn config_path() -> Option<PathBuf> {
+    match std::env::var_os(CONFIG_ENV) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => config_dir().map(|dir| dir.join("config.json")),
+    }
+}
+
+/// Loads the config file, falling back to defaults when there is none
+pub fn load_config() -> Result<Config> {
+    let Some(path) = config_path() else {
+        return Ok(Config::default());
+    };
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let contents = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file '{}'", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("Invalid config file '{}'", path.display()))
+}
@@ -1,28 +1,26 @@
+use crate::output::{FormatSettings, OutputFormat, RowWriter, writer_for};
+use crate::storage::JsonList;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::BufWriter;
 use std::time::Duration;
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-/// Export a vector of records (Vec<Vec<String>>) to CSV
-pub fn export_to_csv(file_path: &str, headers: &[&str], rows: &[&[String]]) -> Result<()> {
-    let mut wtr = csv::Writer::from_path(file_path)?;
-    wtr.write_record(headers)?;
-    for row in rows {
-        wtr.write_record(&row[..])?;
-    }
-    
-    wtr.flush()?;
-    Ok(())
+/// Creates (or truncates) a file that results are written to
+pub fn create_output_file(file_path: &str) -> Result<File> {
+    File::create(file_path).with_context(|| format!("Failed to create output file '{}'", file_path))
 }
 
-/// Export a vector of serializable records to JSON
-pub fn export_to_json<T: Serialize>(file_path: &str, data: &[T]) -> Result<()> {
-    let file = File::create(file_path)?;
-    serde_json::to_writer_pretty(file, data)?;
-    Ok(())
+/// Creates a writer that exports rows to an open file in the given format as they are read.
+///
+/// The writer shares the file's position, so the result sets of a script follow each other.
+pub fn export_to_file(file: &File, format: OutputFormat, settings: &FormatSettings) -> Result<Box<dyn RowWriter>> {
+    Ok(writer_for(format, BufWriter::new(file.try_clone()?), settings))
 }
 
+/// Version of the `benchmark.json` format; files without one are from before it was versioned
+const BENCHMARK_FORMAT_VERSION: u64 = 1;
+
 // Export benchmark.json when profile tag is called
 #[derive(Serialize, Deserialize)]
 struct BenchmarkEntry {
@@ -39,22 +37,9 @@ pub fn save_benchmark_log(sql: &str, elapsed: Duration) -> anyhow::Result<()> {
         timestamp: chrono::Utc::now().to_rfc3339(),
     };
 
-    // Reads existing log or create an empty list
-    let path = "benchmark.json";
-    let mut data = Vec::<BenchmarkEntry>::new();
-
-    if let Ok(mut file) = File::open(path) {
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-        data = serde_json::from_str(&contents).unwrap_or_default();
-    }
-
-    // Add new entry
-    data.push(entry);
-
-    // Write updated log
-    let mut file = File::create(path)?;
-    file.write_all(serde_json::to_string_pretty(&data)?.as_bytes())?;
-
-    Ok(())
+    // Appends under a lock, so concurrent runs keep each other's entries
+    JsonList::new("benchmark.json", "entries", BENCHMARK_FORMAT_VERSION).update(|entries: &mut Vec<BenchmarkEntry>| {
+        entries.push(entry);
+        Ok(())
+    })
 }
\ No newline at end of file
@@ -0,0 +1,231 @@
+use anyhow::{Context, Result};
+use comfy_table::Table;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::storage::lock_file;
+
+/// One statement run from the TUI
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub timestamp: String,
+    pub sql: String,
+    pub milliseconds: f64,
+    /// Rows returned, or rows changed for INSERT/UPDATE/DELETE
+    pub rows: Option<usize>,
+    /// Error message when the statement failed
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(sql: &str, elapsed: Duration, outcome: Result<Option<usize>, String>) -> Self {
+        let (rows, error) = match outcome {
+            Ok(rows) => (rows, None),
+            Err(e) => (None, Some(e)),
+        };
+        HistoryEntry {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            sql: sql.to_string(),
+            milliseconds: elapsed.as_secs_f64() * 1000.0,
+            rows,
+            error,
+        }
+    }
+}
+
+/// Query history of one database, stored as NDJSON so new entries are appended
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<HistoryEntry>,
+    /// Lines that could not be read, e.g. one cut short by a crash while it was appended
+    skipped: usize,
+}
+
+impl History {
+    /// Loads the history for a database; a missing file is an empty history and damaged lines are left out
+    pub fn open(db_path: &str) -> Result<Self> {
+        let path = history_path(db_path);
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        if let Some(path) = &path
+            && path.exists()
+        {
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read history file '{}'", path.display()))?;
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str(line) {
+                    Ok(entry) => entries.push(entry),
+                    Err(_) => skipped += 1,
+                }
+            }
+        }
+        Ok(History { path, entries, skipped })
+    }
+
+    /// A warning about lines left out when the history was loaded, if there were any
+    pub fn skipped_warning(&self) -> Option<String> {
+        let path = self.path.as_ref().filter(|_| self.skipped > 0)?;
+        Some(format!("Skipped {} unreadable line(s) in history file '{}'", self.skipped, path.display()))
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// Adds an entry and appends it to the history file.
+    ///
+    /// The line is written in one call under the file's lock, so TUIs sharing a history do not
+    /// interleave their entries.
+    pub fn record(&mut self, entry: HistoryEntry) -> Result<()> {
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut line = serde_json::to_string(&entry)?;
+            line.push('\n');
+
+            let lock = lock_file(path)?;
+            let mut file = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open history file '{}'", path.display()))?;
+            // Start a new line after one cut short, so this entry is not lost with it
+            if file.metadata()?.len() > 0 {
+                let mut last = [0u8];
+                file.seek(SeekFrom::End(-1))?;
+                file.read_exact(&mut last)?;
+                if last[0] != b'\n' {
+                    line.insert(0, '\n');
+                }
+            }
+            file.write_all(line.as_bytes())
+                .with_context(|| format!("Failed to write history file '{}'", path.display()))?;
+            drop(lock);
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Index of the newest entry before `before` whose SQL contains `needle`, ignoring case
+    pub fn search(&self, needle: &str, before: usize) -> Option<usize> {
+        let needle = needle.to_lowercase();
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.sql.to_lowercase().contains(&needle))
+    }
+}
+
+/// Options for the `history` subcommand
+pub struct HistoryOptions {
+    pub limit: usize,
+    pub search: Option<String>,
+    pub failed: bool,
+    pub json: bool,
+    pub clear: bool,
+}
+
+/// Prints the query history of a database, newest last
+pub fn show_history(db_path: &str, options: &HistoryOptions) -> Result<()> {
+    // Clearing does not read the file, so it also gets rid of a damaged one
+    if options.clear {
+        if let Some(path) = history_path(db_path)
+            && path.exists()
+        {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove history file '{}'", path.display()))?;
+        }
+        println!("Cleared history for '{}'", db_path);
+        return Ok(());
+    }
+
+    let history = History::open(db_path)?;
+    if let Some(warning) = history.skipped_warning() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let needle = options.search.as_ref().map(|s| s.to_lowercase());
+    let matching: Vec<(usize, &HistoryEntry)> = history
+        .entries()
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| !options.failed || entry.error.is_some())
+        .filter(|(_, entry)| needle.as_ref().is_none_or(|n| entry.sql.to_lowercase().contains(n)))
+        .collect();
+    let shown = &matching[matching.len().saturating_sub(options.limit)..];
+
+    if options.json {
+        let entries: Vec<&HistoryEntry> = shown.iter().map(|(_, entry)| *entry).collect();
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if shown.is_empty() {
+        println!("No history for '{}'", db_path);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_header(vec!["#", "Time", "Duration", "Rows", "Status", "SQL"]);
+    for (i, entry) in shown {
+        table.add_row(vec![
+            (i + 1).to_string(),
+            local_time(&entry.timestamp),
+            format!("{:.1} ms", entry.milliseconds),
+            entry.rows.map(|r| r.to_string()).unwrap_or_default(),
+            match &entry.error {
+                Some(e) => format!("error: {}", e),
+                None => "ok".to_string(),
+            },
+            one_line(&entry.sql, 80),
+        ]);
+    }
+    println!("{table}");
+    if shown.len() < matching.len() {
+        println!("Showing last {} of {} entries (use --limit to see more)", shown.len(), matching.len());
+    }
+    Ok(())
+}
+
+/// History file for a database: `<data dir>/rustdb/history/<file name>-<path hash>.ndjson`
+fn history_path(db_path: &str) -> Option<PathBuf> {
+    let file = format!("{}.ndjson", database_key(db_path)?);
+    dirs::data_dir().map(|dir| dir.join("rustdb").join("history").join(file))
+}
+
+/// `<file name>-<path hash>`, naming files kept for one database; `None` for in-memory databases
+pub fn database_key(db_path: &str) -> Option<String> {
+    // In-memory databases have nothing to come back to
+    if db_path.is_empty() || db_path == ":memory:" {
+        return None;
+    }
+    let full_path = fs::canonicalize(db_path).unwrap_or_else(|_| PathBuf::from(db_path));
+    let name = Path::new(db_path).file_name()?.to_string_lossy().to_string();
+    Some(format!("{}-{:016x}", name, fnv1a(full_path.to_string_lossy().as_bytes())))
+}
+
+/// FNV-1a, a hash that stays the same across builds so file names are stable
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+pub fn local_time(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// Collapses whitespace so a statement fits on one table row
+pub fn one_line(sql: &str, max_chars: usize) -> String {
+    let flat = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= max_chars {
+        return flat;
+    }
+    let mut cut: String = flat.chars().take(max_chars - 1).collect();
+    cut.push('…');
+    cut
+}
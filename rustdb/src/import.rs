@@ -0,0 +1,414 @@
+use anyhow::{Context, Result, bail};
+use rusqlite::{Connection, types::Value};
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::sql::quote_ident;
+
+/// Formats that can be imported
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImportFormat {
+    Csv,
+    Tsv,
+    /// JSON array of objects
+    Json,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Whether the first CSV row holds column names
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderMode {
+    /// Treat the first row as a header when it looks like one
+    Auto,
+    Yes,
+    No,
+}
+
+/// Options for reading the input file
+pub struct ImportOptions {
+    pub format: Option<ImportFormat>,
+    pub header: HeaderMode,
+    pub delimiter: Option<u8>,
+    pub quote: u8,
+    pub quoting: bool,
+}
+
+/// Rows read from the input file, ready to insert
+struct Dataset {
+    columns: Vec<String>,
+    /// Parsed rows, or the reason a row could not be read
+    rows: Vec<Result<Vec<Value>, String>>,
+}
+
+/// Imports a CSV, TSV, JSON or NDJSON file into a table, creating the table if it is missing
+pub fn import_file(db_path: &str, file: &str, table: &str, options: &ImportOptions) -> Result<()> {
+    let format = match options.format {
+        Some(format) => format,
+        None => detect_format(file)?,
+    };
+    let contents = read_input(file)?;
+
+    let mut dataset = match format {
+        ImportFormat::Csv | ImportFormat::Tsv => {
+            let default_delimiter = if format == ImportFormat::Tsv { b'\t' } else { b',' };
+            let delimiter = options.delimiter.unwrap_or(default_delimiter);
+            read_csv(&contents, delimiter, options)?
+        }
+        ImportFormat::Json => read_json(&contents)?,
+        ImportFormat::Ndjson => read_ndjson(&contents),
+    };
+
+    if dataset.columns.is_empty() {
+        bail!("No columns found in '{}'", file);
+    }
+
+    let types = infer_types(&dataset);
+    if matches!(format, ImportFormat::Csv | ImportFormat::Tsv) {
+        coerce_text(&mut dataset, &types);
+    }
+
+    let mut conn = Connection::open(db_path)?;
+    let tx = conn.transaction()?;
+
+    let column_defs: Vec<String> = dataset
+        .columns
+        .iter()
+        .zip(&types)
+        .map(|(name, col_type)| format!("{} {}", quote_ident(name), col_type))
+        .collect();
+    tx.execute(
+        &format!("CREATE TABLE IF NOT EXISTS {} ({})", quote_ident(table), column_defs.join(", ")),
+        [],
+    )?;
+
+    let column_list: Vec<String> = dataset.columns.iter().map(|c| quote_ident(c)).collect();
+    let placeholders: Vec<String> = (1..=dataset.columns.len()).map(|i| format!("?{}", i)).collect();
+    let insert_sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        quote_ident(table),
+        column_list.join(", "),
+        placeholders.join(", ")
+    );
+
+    let mut inserted = 0;
+    let mut rejected: Vec<(usize, String)> = Vec::new();
+    {
+        let mut stmt = tx.prepare(&insert_sql)?;
+        for (i, row) in dataset.rows.iter().enumerate() {
+            let result = match row {
+                Ok(values) => stmt
+                    .execute(rusqlite::params_from_iter(values.iter()))
+                    .map_err(|e| e.to_string()),
+                Err(reason) => Err(reason.clone()),
+            };
+            match result {
+                Ok(_) => inserted += 1,
+                Err(reason) => rejected.push((i + 1, reason)),
+            }
+        }
+    }
+    tx.commit()?;
+
+    println!("Imported {} row(s) into '{}'", inserted, table);
+    if !rejected.is_empty() {
+        println!("Rejected {} row(s):", rejected.len());
+        for (row, reason) in rejected.iter().take(10) {
+            println!("  record {}: {}", row, reason);
+        }
+        if rejected.len() > 10 {
+            println!("  ... and {} more", rejected.len() - 10);
+        }
+    }
+
+    Ok(())
+}
+
+/// Picks the input format from the file extension
+fn detect_format(file: &str) -> Result<ImportFormat> {
+    let extension = Path::new(file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("csv") => Ok(ImportFormat::Csv),
+        Some("tsv") | Some("tab") => Ok(ImportFormat::Tsv),
+        Some("json") => Ok(ImportFormat::Json),
+        Some("ndjson") | Some("jsonl") => Ok(ImportFormat::Ndjson),
+        _ => bail!("Cannot tell the format of '{}'; pass --format", file),
+    }
+}
+
+/// Reads the whole input file, or stdin when the path is `-`
+fn read_input(file: &str) -> Result<String> {
+    if file == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).context("Failed to read stdin")?;
+        return Ok(contents);
+    }
+    fs::read_to_string(file).with_context(|| format!("Failed to read '{}'", file))
+}
+
+fn read_csv(contents: &str, delimiter: u8, options: &ImportOptions) -> Result<Dataset> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .quote(options.quote)
+        .quoting(options.quoting)
+        .from_reader(contents.as_bytes());
+
+    // A record that cannot be read is rejected like any other bad row, without stopping the import
+    let mut records: Vec<Result<Vec<String>, String>> = reader
+        .records()
+        .map(|record| record.map(|r| r.iter().map(String::from).collect()).map_err(|e| e.to_string()))
+        .collect();
+    if records.is_empty() {
+        return Ok(Dataset { columns: Vec::new(), rows: Vec::new() });
+    }
+
+    let has_header = match (options.header, &records[0]) {
+        (HeaderMode::Yes, _) => true,
+        (HeaderMode::No, _) => false,
+        (HeaderMode::Auto, Ok(first)) => {
+            let header = looks_like_header(first);
+            if header {
+                println!("Using the first row as column names (pass --header no to import it as data)");
+            } else {
+                println!("Importing the first row as data, as it does not look like column names (pass --header yes to use it)");
+            }
+            header
+        }
+        (HeaderMode::Auto, Err(_)) => false,
+    };
+
+    let columns: Vec<String> = if has_header {
+        records.remove(0).map_err(|e| anyhow::anyhow!("Cannot read the header row: {}", e))?
+    } else {
+        let width = records.iter().flatten().next().map_or(0, Vec::len);
+        (1..=width).map(|i| format!("c{}", i)).collect()
+    };
+
+    let rows = records
+        .into_iter()
+        .map(|record| {
+            let record = record?;
+            if record.len() != columns.len() {
+                return Err(format!("expected {} fields, found {}", columns.len(), record.len()));
+            }
+            Ok(record.into_iter().map(Value::Text).collect())
+        })
+        .collect();
+
+    Ok(Dataset { columns, rows })
+}
+
+/// A header row has distinct, non-empty, non-numeric cells
+fn looks_like_header(record: &[String]) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    record.iter().all(|cell| {
+        let cell = cell.trim();
+        !cell.is_empty() && cell.parse::<f64>().is_err() && seen.insert(cell.to_lowercase())
+    })
+}
+
+fn read_json(contents: &str) -> Result<Dataset> {
+    let parsed: JsonValue = serde_json::from_str(contents).context("Invalid JSON")?;
+    let JsonValue::Array(items) = parsed else {
+        bail!("Expected a JSON array of objects");
+    };
+    Ok(objects_to_dataset(items.into_iter().map(Ok).collect()))
+}
+
+fn read_ndjson(contents: &str) -> Dataset {
+    let items = contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str::<JsonValue>(line).map_err(|e| format!("invalid JSON: {}", e)))
+        .collect();
+    objects_to_dataset(items)
+}
+
+/// Collects the keys of every object, in order of first appearance, and lines rows up with them
+fn objects_to_dataset(items: Vec<Result<JsonValue, String>>) -> Dataset {
+    let mut columns: Vec<String> = Vec::new();
+    for item in items.iter().flatten() {
+        if let JsonValue::Object(object) = item {
+            for key in object.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+    }
+
+    let rows = items
+        .into_iter()
+        .map(|item| match item? {
+            JsonValue::Object(object) => Ok(columns
+                .iter()
+                .map(|column| object.get(column).map(json_to_value).unwrap_or(Value::Null))
+                .collect()),
+            other => Err(format!("expected an object, found {}", json_kind(&other))),
+        })
+        .collect();
+
+    Dataset { columns, rows }
+}
+
+fn json_to_value(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Integer(*b as i64),
+        JsonValue::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => n.as_f64().map(Value::Real).unwrap_or(Value::Null),
+        },
+        JsonValue::String(s) => Value::Text(s.clone()),
+        // Nested values are stored as JSON text, which SQLite's json functions understand
+        other => Value::Text(other.to_string()),
+    }
+}
+
+fn json_kind(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "a boolean",
+        JsonValue::Number(_) => "a number",
+        JsonValue::String(_) => "a string",
+        JsonValue::Array(_) => "an array",
+        JsonValue::Object(_) => "an object",
+    }
+}
+
+/// Picks INTEGER, REAL or TEXT for each column from the values it holds
+fn infer_types(dataset: &Dataset) -> Vec<&'static str> {
+    (0..dataset.columns.len())
+        .map(|i| {
+            let mut any_value = false;
+            let mut all_integer = true;
+            let mut all_numeric = true;
+            for row in dataset.rows.iter().flatten() {
+                match &row[i] {
+                    Value::Null => continue,
+                    Value::Text(t) if t.is_empty() => continue,
+                    Value::Integer(_) => {}
+                    Value::Real(_) => all_integer = false,
+                    Value::Text(t) => {
+                        if t.parse::<i64>().is_err() {
+                            all_integer = false;
+                            if t.parse::<f64>().is_err() {
+                                all_numeric = false;
+                            }
+                        }
+                    }
+                    Value::Blob(_) => {
+                        all_integer = false;
+                        all_numeric = false;
+                    }
+                }
+                any_value = true;
+            }
+            if !any_value {
+                "TEXT"
+            } else if all_integer {
+                "INTEGER"
+            } else if all_numeric {
+                "REAL"
+            } else {
+                "TEXT"
+            }
+        })
+        .collect()
+}
+
+/// Converts CSV text cells in numeric columns to numbers, and empty ones to NULL
+fn coerce_text(dataset: &mut Dataset, types: &[&str]) {
+    for row in dataset.rows.iter_mut().flatten() {
+        for (value, col_type) in row.iter_mut().zip(types) {
+            let Value::Text(text) = value else { continue };
+            let coerced = match *col_type {
+                "INTEGER" | "REAL" if text.is_empty() => Value::Null,
+                "INTEGER" => text.parse::<i64>().map(Value::Integer).unwrap_or(Value::Null),
+                "REAL" => text.parse::<f64>().map(Value::Real).unwrap_or(Value::Null),
+                _ => continue,
+            };
+            *value = coerced;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset(columns: &[&str], rows: Vec<Vec<Value>>) -> Dataset {
+        Dataset { columns: columns.iter().map(|c| c.to_string()).collect(), rows: rows.into_iter().map(Ok).collect() }
+    }
+
+    fn text(t: &str) -> Value {
+        Value::Text(t.to_string())
+    }
+
+    fn record(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn recognises_header_rows() {
+        assert!(looks_like_header(&record(&["id", "name", "price"])));
+        assert!(!looks_like_header(&record(&["1", "Alice", "2.5"])));
+        assert!(!looks_like_header(&record(&["id", ""])));
+        // Repeated names, even in another case, make poor column names
+        assert!(!looks_like_header(&record(&["a", "A"])));
+        assert!(!looks_like_header(&record(&["name", "-1e3"])));
+    }
+
+    #[test]
+    fn infers_the_narrowest_column_types() {
+        let data = dataset(
+            &["int", "real", "text", "empty", "mixed"],
+            vec![
+                vec![text("1"), text("1"), text("a"), text(""), Value::Integer(1)],
+                vec![text(""), text("2.5"), text("3"), Value::Null, Value::Real(0.5)],
+                vec![text("-3"), text("1e3"), text(""), text(""), text("x")],
+            ],
+        );
+        assert_eq!(infer_types(&data), ["INTEGER", "REAL", "TEXT", "TEXT", "TEXT"]);
+    }
+
+    #[test]
+    fn rejected_rows_do_not_affect_types() {
+        let mut data = dataset(&["n"], vec![vec![text("1")]]);
+        data.rows.push(Err("expected 1 fields, found 2".into()));
+        assert_eq!(infer_types(&data), ["INTEGER"]);
+    }
+
+    #[test]
+    fn coerces_text_to_column_types() {
+        let mut data = dataset(
+            &["int", "real", "text"],
+            vec![vec![text("42"), text("2.5"), text("007")], vec![text(""), text(""), text("")]],
+        );
+        coerce_text(&mut data, &["INTEGER", "REAL", "TEXT"]);
+        let rows: Vec<Vec<Value>> = data.rows.into_iter().flatten().collect();
+        assert_eq!(rows[0], [Value::Integer(42), Value::Real(2.5), text("007")]);
+        assert_eq!(rows[1], [Value::Null, Value::Null, text("")]);
+    }
+
+    #[test]
+    fn reads_csv_with_a_detected_header() {
+        let options = ImportOptions { format: None, header: HeaderMode::Auto, delimiter: None, quote: b'"', quoting: true };
+        let data = read_csv("id,name\n1,\"Smith, J\"\n2\n", b',', &options).unwrap();
+        assert_eq!(data.columns, ["id", "name"]);
+        assert_eq!(data.rows[0], Ok(vec![text("1"), text("Smith, J")]));
+        assert!(data.rows[1].is_err());
+
+        let options = ImportOptions { header: HeaderMode::No, ..options };
+        let data = read_csv("a,b\n", b',', &options).unwrap();
+        assert_eq!(data.columns, ["c1", "c2"]);
+        assert_eq!(data.rows, [Ok(vec![text("a"), text("b")])]);
+    }
+}
@@ -1,27 +1,147 @@
 mod cli;
+mod config;
 mod query;
 mod analyze;
+mod bench;
+mod bundle;
 mod export;
+mod history;
+mod import;
+mod output;
 mod tui;
 mod saved_queries;
+mod params;
+mod script;
+mod storage;
+mod sql;
+mod stats;
 
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, QueryArgs, SavedCommand};
+use saved_queries::SavedStores;
 
 fn main() -> anyhow:: Result<()> {
     let args = Cli::parse();
+    let saved_file = args.saved_queries.as_deref();
 
     match args.command {
-        Commands::Query { db_path, sql, csv, json, explain, profile} => {
-            query::run_query(&db_path, &sql, csv.as_deref(), json.as_deref(), explain, profile)?;
+        Commands::Query { db_path, sql, file, saved, settings, options } => {
+            // clap drops `requires` for arguments that conflict with one given, so check here
+            if !settings.is_empty() && saved.is_none() {
+                anyhow::bail!("--set only applies to saved queries; use --named for other SQL");
+            }
+            let (sql, bound) = match (sql, file, saved) {
+                (Some(sql), _, _) => (sql, Vec::new()),
+                (None, Some(path), _) => (script::read_script(&path)?, Vec::new()),
+                (None, None, Some(name)) => {
+                    let saved = SavedStores::locate(saved_file, Some(&db_path)).find(&name, None)?.query;
+                    let bound = saved.bind_values(&settings)?;
+                    (saved.sql, bound)
+                }
+                (None, None, None) => anyhow::bail!("Provide SQL text, --file or --saved"),
+            };
+            query::run_query(&db_path, &sql, &query_options(&options, bound))?;
         }
-        Commands::Analyze { db_path, table } => {
-            analyze::analyze_table(&db_path, &table)?;
+        Commands::Run { db_path, name, settings, options } => {
+            let saved = SavedStores::locate(saved_file, Some(&db_path)).find(&name, None)?.query;
+            let bound = saved.bind_values(&settings)?;
+            query::run_query(&db_path, &saved.sql, &query_options(&options, bound))?;
+        }
+        Commands::Bench { db_path, sql, runs, warmup, cold, params, named } => {
+            let options = bench::BenchOptions {
+                runs: runs as usize,
+                warmup: warmup as usize,
+                cold,
+                params: params::QueryParams { positional: params, named },
+            };
+            bench::bench(&db_path, &sql, &options)?;
+        }
+        Commands::Analyze { db_path, table, all, include, exclude, json, top, attach } => {
+            let options = analyze::AnalyzeOptions { table, attach, include, exclude, internal: all, json, top };
+            analyze::analyze(&db_path, &options)?;
+        }
+        Commands::Import { db_path, file, table, format, header, delimiter, quote, no_quoting } => {
+            let options = import::ImportOptions {
+                format,
+                header,
+                delimiter: delimiter.map(ascii_byte).transpose()?,
+                quote: ascii_byte(quote)?,
+                quoting: !no_quoting,
+            };
+            import::import_file(&db_path, &file, &table, &options)?;
         }
         Commands::Tui { db_path } => {
-            tui::start_tui(&db_path)?;
+            tui::start_tui(&db_path, SavedStores::locate(saved_file, Some(&db_path)))?;
+        }
+        Commands::History { db_path, limit, search, failed, json, clear } => {
+            let options = history::HistoryOptions { limit, search, failed, json, clear };
+            history::show_history(&db_path, &options)?;
+        }
+        Commands::Saved { db, scope, command } => {
+            let stores = SavedStores::locate(saved_file, db.as_deref());
+            match command {
+                SavedCommand::List { tag, json } => saved_queries::list_saved(&stores, tag.as_deref(), scope, json)?,
+                SavedCommand::Show { name } => saved_queries::show_saved(&stores, &name, scope)?,
+                SavedCommand::Add { name, sql, description, tags, params, force } => {
+                    let description = description.filter(|d| !d.trim().is_empty());
+                    let tags = tags.iter().flat_map(|t| saved_queries::parse_tags(t)).collect();
+                    let params = saved_queries::declare_params(&sql, &params)?;
+                    let query = saved_queries::SavedQuery { description, tags, params, ..saved_queries::SavedQuery::new(&name, &sql) };
+                    let scope = stores.add(query, scope, force)?;
+                    println!("Saved query '{}' ({})", name.trim(), scope.name());
+                }
+                SavedCommand::Update { name, sql, description, tags, clear_tags, params } => {
+                    let tags = tags.iter().flat_map(|t| saved_queries::parse_tags(t)).collect::<Vec<_>>();
+                    let tags = if clear_tags || !tags.is_empty() { Some(tags) } else { None };
+                    let params = if params.is_empty() { None } else { Some(params) };
+                    let changes = saved_queries::QueryChanges { sql, description, tags, params };
+                    let scope = stores.update(&name, scope, changes)?;
+                    println!("Updated saved query '{}' ({})", name.trim(), scope.name());
+                }
+                SavedCommand::Rename { name, new_name } => {
+                    let scope = stores.rename(&name, scope, &new_name)?;
+                    println!("Renamed saved query '{}' to '{}' ({})", name.trim(), new_name.trim(), scope.name());
+                }
+                SavedCommand::Delete { name } => {
+                    let scope = stores.delete(&name, scope)?;
+                    println!("Deleted saved query '{}' ({})", name.trim(), scope.name());
+                }
+                SavedCommand::Export { path, names, tag, format, force } => {
+                    bundle::export_bundle(&stores, scope, &names, tag.as_deref(), &path, format, force)?;
+                }
+                SavedCommand::Import { path, on_conflict, format } => {
+                    bundle::import_bundle(&stores, scope, &path, format, on_conflict)?;
+                }
+            }
         }
     }
 
     Ok(())
 }
+
+/// Query options from the flags shared by `query` and `run`; `--named` values follow, and so override, a saved query's
+fn query_options(args: &QueryArgs, saved_params: Vec<(String, rusqlite::types::Value)>) -> query::QueryOptions<'_> {
+    query::QueryOptions {
+        format: args.format,
+        output: args.output.as_deref(),
+        csv_file: args.csv.as_deref(),
+        json_file: args.json.as_deref(),
+        settings: output::FormatSettings {
+            blobs: args.blob_encoding,
+            max_rows: args.max_rows,
+            table_name: args.table_name.clone(),
+        },
+        explain: args.explain,
+        profile: args.profile,
+        params: params::QueryParams { positional: args.params.clone(), named: [saved_params, args.named.clone()].concat() },
+        continue_on_error: args.continue_on_error,
+    }
+}
+
+/// CSV options are single bytes, so only ASCII characters are accepted
+fn ascii_byte(c: char) -> anyhow::Result<u8> {
+    if !c.is_ascii() {
+        anyhow::bail!("'{}' is not an ASCII character", c);
+    }
+    Ok(c as u8)
+}
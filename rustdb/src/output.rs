@@ -0,0 +1,532 @@
+use anyhow::Result;
+use base64::{Engine, prelude::BASE64_STANDARD};
+use comfy_table::{Table, presets::UTF8_FULL};
+use rusqlite::{Statement, types::ValueRef};
+use serde_json::{Map, Number, Value as JsonValue};
+use std::io::Write;
+
+use crate::sql::{quote_ident, quote_literal};
+
+/// Receives a result set one row at a time
+pub trait RowWriter {
+    /// Called once with the result set's column names before any rows
+    fn begin(&mut self, columns: &[String]) -> Result<()>;
+
+    /// Called for every row, in order
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()>;
+
+    /// Called after the last row; flushes anything still buffered
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Output formats for query results
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Pretty table, buffered up to the row cap
+    Table,
+    Csv,
+    Tsv,
+    /// JSON array of objects keyed by column name
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// GitHub-flavored Markdown table
+    Markdown,
+    Html,
+    /// SQL INSERT statements
+    Insert,
+    /// `|` separated rows
+    Text,
+}
+
+/// How BLOB values are encoded in JSON output
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum BlobEncoding {
+    Base64,
+    Hex,
+}
+
+/// Settings shared by all output formats
+#[derive(Clone, Debug)]
+pub struct FormatSettings {
+    pub blobs: BlobEncoding,
+    /// Rows buffered by the table view
+    pub max_rows: usize,
+    /// Target table for INSERT statements
+    pub table_name: String,
+}
+
+/// Builds a writer for the given format
+pub fn writer_for<W: Write + 'static>(format: OutputFormat, out: W, settings: &FormatSettings) -> Box<dyn RowWriter> {
+    match format {
+        OutputFormat::Table => Box::new(TableWriter::new(out, settings.max_rows)),
+        OutputFormat::Csv => Box::new(CsvWriter::new(out)),
+        OutputFormat::Tsv => Box::new(CsvWriter::with_delimiter(out, b'\t')),
+        OutputFormat::Json => Box::new(JsonWriter::new(out, settings.blobs)),
+        OutputFormat::Ndjson => Box::new(NdjsonWriter::new(out, settings.blobs)),
+        OutputFormat::Markdown => Box::new(MarkdownWriter::new(out)),
+        OutputFormat::Html => Box::new(HtmlWriter::new(out)),
+        OutputFormat::Insert => Box::new(InsertWriter::new(out, &settings.table_name)),
+        OutputFormat::Text => Box::new(TextWriter::new(out)),
+    }
+}
+
+/// Feeds every row of a bound statement to the writers and returns the row count
+pub fn write_rows(stmt: &mut Statement, writers: &mut [&mut (dyn RowWriter + 'static)]) -> Result<usize> {
+    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
+    let column_count = column_names.len();
+
+    for writer in writers.iter_mut() {
+        writer.begin(&column_names)?;
+    }
+
+    let mut count = 0;
+    let mut rows = stmt.raw_query();
+    while let Some(row) = rows.next()? {
+        let values = (0..column_count)
+            .map(|i| row.get_ref(i))
+            .collect::<rusqlite::Result<Vec<ValueRef>>>()?;
+        for writer in writers.iter_mut() {
+            writer.write_row(&values)?;
+        }
+        count += 1;
+    }
+
+    for writer in writers.iter_mut() {
+        writer.finish()?;
+    }
+
+    Ok(count)
+}
+
+/// Pretty `comfy_table` output, buffering at most `max_rows` rows
+pub struct TableWriter<W: Write> {
+    out: W,
+    table: Table,
+    max_rows: usize,
+    total_rows: usize,
+}
+
+impl<W: Write> TableWriter<W> {
+    pub fn new(out: W, max_rows: usize) -> Self {
+        let mut table = Table::new();
+        table.load_preset(UTF8_FULL);
+        TableWriter { out, table, max_rows, total_rows: 0 }
+    }
+}
+
+impl<W: Write> RowWriter for TableWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.table.set_header(columns);
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        // Rows past the cap are only counted
+        if self.total_rows < self.max_rows {
+            self.table.add_row(row.iter().map(value_to_string).collect::<Vec<_>>());
+        }
+        self.total_rows += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        writeln!(self.out, "{}", self.table)?;
+        if self.total_rows > self.max_rows {
+            writeln!(
+                self.out,
+                "Showing first {} of {} rows (raise --max-rows or use another --format to see all)",
+                self.max_rows, self.total_rows
+            )?;
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Delimiter separated values with a header row
+pub struct CsvWriter<W: Write> {
+    wtr: csv::Writer<W>,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self::with_delimiter(out, b',')
+    }
+
+    pub fn with_delimiter(out: W, delimiter: u8) -> Self {
+        let wtr = csv::WriterBuilder::new().delimiter(delimiter).from_writer(out);
+        CsvWriter { wtr }
+    }
+}
+
+impl<W: Write> RowWriter for CsvWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.wtr.write_record(columns)?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        self.wtr.write_record(row.iter().map(value_to_string))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.wtr.flush()?;
+        Ok(())
+    }
+}
+
+/// One JSON object per line, keyed by column name
+pub struct NdjsonWriter<W: Write> {
+    out: W,
+    columns: Vec<String>,
+    blobs: BlobEncoding,
+}
+
+impl<W: Write> NdjsonWriter<W> {
+    pub fn new(out: W, blobs: BlobEncoding) -> Self {
+        NdjsonWriter { out, columns: Vec::new(), blobs }
+    }
+}
+
+impl<W: Write> RowWriter for NdjsonWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        serde_json::to_writer(&mut self.out, &row_to_json(&self.columns, row, self.blobs))?;
+        self.out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A pretty-printed JSON array of objects keyed by column name
+pub struct JsonWriter<W: Write> {
+    out: W,
+    columns: Vec<String>,
+    blobs: BlobEncoding,
+    rows_written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(out: W, blobs: BlobEncoding) -> Self {
+        JsonWriter { out, columns: Vec::new(), blobs, rows_written: 0 }
+    }
+}
+
+impl<W: Write> RowWriter for JsonWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        self.out.write_all(b"[")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        if self.rows_written > 0 {
+            self.out.write_all(b",")?;
+        }
+        let object = serde_json::to_string_pretty(&row_to_json(&self.columns, row, self.blobs))?;
+        // Indent each object one level inside the array
+        write!(self.out, "\n  {}", object.replace('\n', "\n  "))?;
+        self.rows_written += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.rows_written > 0 {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.write_all(b"]\n")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Plain `|` separated rows, like the sqlite3 shell's list mode
+pub struct TextWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> TextWriter<W> {
+    pub fn new(out: W) -> Self {
+        TextWriter { out }
+    }
+}
+
+impl<W: Write> RowWriter for TextWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        writeln!(self.out, "{}", columns.join("|"))?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        let line: Vec<String> = row.iter().map(value_to_string).collect();
+        writeln!(self.out, "{}", line.join("|"))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// A GitHub-flavored Markdown table
+pub struct MarkdownWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> MarkdownWriter<W> {
+    pub fn new(out: W) -> Self {
+        MarkdownWriter { out }
+    }
+
+    fn write_line(&mut self, cells: &[String]) -> Result<()> {
+        let cells: Vec<String> = cells
+            .iter()
+            .map(|c| c.replace('|', "\\|").replace('\n', "<br>"))
+            .collect();
+        writeln!(self.out, "| {} |", cells.join(" | "))?;
+        Ok(())
+    }
+}
+
+impl<W: Write> RowWriter for MarkdownWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.write_line(columns)?;
+        writeln!(self.out, "|{}", " --- |".repeat(columns.len()))?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        let cells: Vec<String> = row.iter().map(value_to_string).collect();
+        self.write_line(&cells)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// An HTML `<table>` fragment
+pub struct HtmlWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> HtmlWriter<W> {
+    pub fn new(out: W) -> Self {
+        HtmlWriter { out }
+    }
+}
+
+impl<W: Write> RowWriter for HtmlWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        writeln!(self.out, "<table>")?;
+        writeln!(self.out, "  <thead>")?;
+        let cells: String = columns.iter().map(|c| format!("<th>{}</th>", escape_html(c))).collect();
+        writeln!(self.out, "    <tr>{}</tr>", cells)?;
+        writeln!(self.out, "  </thead>")?;
+        writeln!(self.out, "  <tbody>")?;
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        let cells: String = row
+            .iter()
+            .map(|v| format!("<td>{}</td>", escape_html(&value_to_string(v))))
+            .collect();
+        writeln!(self.out, "    <tr>{}</tr>", cells)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        writeln!(self.out, "  </tbody>")?;
+        writeln!(self.out, "</table>")?;
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// One SQL `INSERT` statement per row
+pub struct InsertWriter<W: Write> {
+    out: W,
+    table_name: String,
+    column_list: String,
+}
+
+impl<W: Write> InsertWriter<W> {
+    pub fn new(out: W, table_name: &str) -> Self {
+        InsertWriter { out, table_name: quote_ident(table_name), column_list: String::new() }
+    }
+}
+
+impl<W: Write> RowWriter for InsertWriter<W> {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        self.column_list = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        let values: Vec<String> = row.iter().map(quote_literal).collect();
+        writeln!(
+            self.out,
+            "INSERT INTO {} ({}) VALUES ({});",
+            self.table_name,
+            self.column_list,
+            values.join(", ")
+        )?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+/// Converts all types to String properly
+pub fn value_to_string(value: &ValueRef) -> String {
+    match value {
+        ValueRef::Integer(v) => v.to_string(),
+        ValueRef::Real(v) => v.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
+        ValueRef::Blob(_) => "<BLOB>".to_string(),
+        ValueRef::Null => "NULL".to_string(),
+    }
+}
+
+/// Builds one JSON object for a row, keeping the result set's column order
+fn row_to_json(columns: &[String], row: &[ValueRef], blobs: BlobEncoding) -> JsonValue {
+    let object: Map<String, JsonValue> = columns
+        .iter()
+        .zip(row)
+        .map(|(name, value)| (name.clone(), value_to_json(value, blobs)))
+        .collect();
+    JsonValue::Object(object)
+}
+
+/// Converts a SQLite value to its native JSON counterpart
+fn value_to_json(value: &ValueRef, blobs: BlobEncoding) -> JsonValue {
+    match value {
+        ValueRef::Null => JsonValue::Null,
+        ValueRef::Integer(i) => JsonValue::from(*i),
+        // NaN and infinities have no JSON representation
+        ValueRef::Real(r) => Number::from_f64(*r).map(JsonValue::Number).unwrap_or(JsonValue::Null),
+        ValueRef::Text(t) => JsonValue::String(String::from_utf8_lossy(t).to_string()),
+        ValueRef::Blob(b) => JsonValue::String(encode_blob(b, blobs)),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn encode_blob(bytes: &[u8], blobs: BlobEncoding) -> String {
+    match blobs {
+        BlobEncoding::Base64 => BASE64_STANDARD.encode(bytes),
+        BlobEncoding::Hex => bytes.iter().map(|b| format!("{:02x}", b)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds a result set through a writer as `write_rows` would
+    fn feed(writer: &mut dyn RowWriter, columns: &[&str], rows: &[Vec<ValueRef>]) {
+        let columns: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+        writer.begin(&columns).unwrap();
+        for row in rows {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn numbers(count: i64) -> Vec<Vec<ValueRef<'static>>> {
+        (1..=count).map(|n| vec![ValueRef::Integer(n * 111)]).collect()
+    }
+
+    #[test]
+    fn table_view_counts_rows_past_its_cap() {
+        let mut out = Vec::new();
+        feed(&mut TableWriter::new(&mut out, 2), &["n"], &numbers(5));
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("111") && text.contains("222"));
+        assert!(!text.contains("333") && !text.contains("555"));
+        assert!(text.contains("Showing first 2 of 5 rows"));
+
+        let mut out = Vec::new();
+        feed(&mut TableWriter::new(&mut out, 5), &["n"], &numbers(5));
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("555") && !text.contains("Showing first"));
+    }
+
+    #[test]
+    fn json_is_an_array_of_typed_objects() {
+        let rows = vec![
+            vec![ValueRef::Integer(1), ValueRef::Text(b"a\"b"), ValueRef::Blob(&[1, 2])],
+            vec![ValueRef::Real(2.5), ValueRef::Null, ValueRef::Real(f64::NAN)],
+        ];
+        let mut out = Vec::new();
+        feed(&mut JsonWriter::new(&mut out, BlobEncoding::Base64), &["id", "name", "data"], &rows);
+        let parsed: JsonValue = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::json!([
+                {"id": 1, "name": "a\"b", "data": "AQI="},
+                {"id": 2.5, "name": null, "data": null},
+            ])
+        );
+
+        let mut out = Vec::new();
+        feed(&mut JsonWriter::new(&mut out, BlobEncoding::Hex), &["n", "b"], &[vec![ValueRef::Integer(1), ValueRef::Blob(&[255])]]);
+        assert_eq!(String::from_utf8(out).unwrap(), "[\n  {\n    \"n\": 1,\n    \"b\": \"ff\"\n  }\n]\n");
+    }
+
+    #[test]
+    fn empty_json_result_is_an_empty_array() {
+        let mut out = Vec::new();
+        feed(&mut JsonWriter::new(&mut out, BlobEncoding::Base64), &["n"], &[]);
+        assert_eq!(String::from_utf8(out).unwrap(), "[]\n");
+    }
+
+    #[test]
+    fn ndjson_writes_one_object_per_line() {
+        let mut out = Vec::new();
+        feed(&mut NdjsonWriter::new(&mut out, BlobEncoding::Base64), &["n", "s"], &[
+            vec![ValueRef::Integer(1), ValueRef::Text(b"x")],
+            vec![ValueRef::Integer(2), ValueRef::Null],
+        ]);
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"n\":1,\"s\":\"x\"}\n{\"n\":2,\"s\":null}\n");
+
+        let mut out = Vec::new();
+        feed(&mut NdjsonWriter::new(&mut out, BlobEncoding::Base64), &["n"], &[]);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn insert_statements_quote_names_and_values() {
+        let mut out = Vec::new();
+        feed(&mut InsertWriter::new(&mut out, "my \"table\""), &["id", "note"], &[
+            vec![ValueRef::Integer(1), ValueRef::Text(b"it's")],
+            vec![ValueRef::Real(2.0), ValueRef::Null],
+        ]);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "INSERT INTO \"my \"\"table\"\"\" (\"id\", \"note\") VALUES (1, 'it''s');\n\
+             INSERT INTO \"my \"\"table\"\"\" (\"id\", \"note\") VALUES (2.0, NULL);\n"
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use anyhow::{Context, Result, anyhow, bail};
+use rusqlite::{Statement, types::Value};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+
+use crate::script::closing_quote;
+
+/// Values bound to a statement's `?NNN` and `:name` placeholders
+#[derive(Debug, Clone, Default)]
+pub struct QueryParams {
+    pub positional: Vec<Value>,
+    pub named: Vec<(String, Value)>,
+}
+
+/// Parses a command line value, inferring its SQLite type.
+///
+/// `NULL` becomes NULL, whole numbers become INTEGER, other finite numbers
+/// become REAL, `@path` reads the file as a BLOB and everything else is TEXT.
+/// Numbers with a leading zero, such as `007`, stay TEXT.
+/// A leading `@@` escapes a literal `@`.
+pub fn parse_value(raw: &str) -> Result<Value> {
+    if raw.eq_ignore_ascii_case("NULL") {
+        return Ok(Value::Null);
+    }
+    if let Some(rest) = raw.strip_prefix("@@") {
+        return Ok(Value::Text(format!("@{}", rest)));
+    }
+    if let Some(path) = raw.strip_prefix('@') {
+        let bytes = fs::read(path).with_context(|| format!("Failed to read blob parameter from '{}'", path))?;
+        return Ok(Value::Blob(bytes));
+    }
+    // Codes like zip codes and phone numbers would lose their leading zeros as numbers
+    let digits = raw.strip_prefix(['-', '+']).unwrap_or(raw).as_bytes();
+    if digits.len() > 1 && digits[0] == b'0' && digits[1].is_ascii_digit() {
+        return Ok(Value::Text(raw.to_string()));
+    }
+    if let Ok(i) = raw.parse::<i64>() {
+        return Ok(Value::Integer(i));
+    }
+    // Rust accepts "inf" and "NaN", which are better kept as text
+    if let Ok(r) = raw.parse::<f64>()
+        && r.is_finite()
+    {
+        return Ok(Value::Real(r));
+    }
+    Ok(Value::Text(raw.to_string()))
+}
+
+/// Parses a `name=value` pair for a named parameter
+pub fn parse_named(raw: &str) -> Result<(String, Value)> {
+    let (name, value) = raw
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected name=value, got '{}'", raw))?;
+    if name.is_empty() {
+        bail!("Parameter name is empty in '{}'", raw);
+    }
+    Ok((name.to_string(), parse_value(value)?))
+}
+
+/// Binds one set of parameters across the statements of a script.
+///
+/// Each statement takes the named values for the placeholders it has, and the next unused
+/// positional values for its `?` and `?NNN` placeholders, so `?1` in the second statement is the
+/// first value the first statement did not take. A placeholder without a value is an error when
+/// its statement is bound. Values that no statement takes are an error too: a single statement
+/// reports it when it is bound, before it runs, and a script from `finish`.
+pub struct ParamBinder<'a> {
+    params: &'a QueryParams,
+    /// Whether there is only one statement, which can be checked as soon as it is bound
+    single: bool,
+    /// Positional values taken by earlier statements
+    offset: usize,
+    /// Which named values a statement has taken
+    used: Vec<bool>,
+}
+
+impl<'a> ParamBinder<'a> {
+    pub fn new(params: &'a QueryParams, statements: usize) -> Self {
+        ParamBinder { params, single: statements == 1, offset: 0, used: vec![false; params.named.len()] }
+    }
+
+    /// Binds the values for one statement's placeholders
+    pub fn bind(&mut self, stmt: &mut Statement) -> Result<()> {
+        let mut positional = 0;
+        for index in 1..=stmt.parameter_count() {
+            match stmt.parameter_name(index).map(str::to_string) {
+                Some(placeholder) if !placeholder.starts_with('?') => {
+                    // A name given twice takes the later value, as with any other option
+                    let mut value = None;
+                    for (i, (name, named)) in self.params.named.iter().enumerate() {
+                        if names_match(name, &placeholder) {
+                            self.used[i] = true;
+                            value = Some(named);
+                        }
+                    }
+                    match value {
+                        Some(value) => stmt.raw_bind_parameter(index, value)?,
+                        None => bail!("No value for parameter '{}'", placeholder),
+                    }
+                }
+                // `?`, `?NNN` and numbers skipped by `?NNN`, which SQLite leaves unnamed
+                _ => {
+                    let Some(value) = self.params.positional.get(self.offset + positional) else {
+                        bail!(
+                            "No value for parameter ?{}: the SQL needs at least {} positional value(s) but {} were given",
+                            index,
+                            self.offset + index,
+                            self.params.positional.len()
+                        );
+                    };
+                    stmt.raw_bind_parameter(index, value)?;
+                    positional += 1;
+                }
+            }
+        }
+        self.offset += positional;
+
+        if self.single {
+            self.finish()?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the statements bound so far took every value
+    pub fn finish(&self) -> Result<()> {
+        if self.params.positional.len() > self.offset {
+            bail!(
+                "{} positional parameter(s) given but the SQL only has {}",
+                self.params.positional.len(),
+                self.offset
+            );
+        }
+        if let Some(i) = self.used.iter().position(|used| !used) {
+            bail!("The SQL has no parameter named '{}'", self.params.named[i].0);
+        }
+        Ok(())
+    }
+}
+
+/// Whether a value's name, with or without its `:`, `@` or `$` prefix, is a statement's placeholder
+fn names_match(name: &str, placeholder: &str) -> bool {
+    if name.starts_with([':', '@', '$']) {
+        name == placeholder
+    } else {
+        &placeholder[1..] == name
+    }
+}
+
+/// A named placeholder declared on a saved query, e.g. `start_date:date=2024-01-01`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParamSpec {
+    /// Name without its `:`, `@` or `$` prefix
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: ParamType,
+    /// Used when no value is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+impl ParamSpec {
+    pub fn new(name: &str) -> Self {
+        ParamSpec { name: name.to_string(), kind: ParamType::Text, default: None }
+    }
+}
+
+impl fmt::Display for ParamSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.name, self.kind.name())?;
+        if let Some(default) = &self.default {
+            write!(f, "={}", default)?;
+        }
+        Ok(())
+    }
+}
+
+/// Types a saved query parameter's value is checked against and bound as
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParamType {
+    #[default]
+    Text,
+    Integer,
+    Real,
+    /// `YYYY-MM-DD`, bound as TEXT
+    Date,
+    /// true/false, yes/no or 1/0, bound as 1 or 0
+    Boolean,
+}
+
+impl ParamType {
+    const ALL: [ParamType; 5] = [ParamType::Text, ParamType::Integer, ParamType::Real, ParamType::Date, ParamType::Boolean];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParamType::Text => "text",
+            ParamType::Integer => "integer",
+            ParamType::Real => "real",
+            ParamType::Date => "date",
+            ParamType::Boolean => "boolean",
+        }
+    }
+
+    /// Checks a value typed by the user and converts it to what gets bound
+    pub fn parse(self, raw: &str) -> Result<Value> {
+        let trimmed = raw.trim();
+        match self {
+            ParamType::Text => Ok(Value::Text(raw.to_string())),
+            ParamType::Integer => trimmed
+                .parse::<i64>()
+                .map(Value::Integer)
+                .map_err(|_| anyhow!("'{}' is not an integer", raw)),
+            ParamType::Real => match trimmed.parse::<f64>() {
+                Ok(r) if r.is_finite() => Ok(Value::Real(r)),
+                _ => bail!("'{}' is not a number", raw),
+            },
+            ParamType::Date => chrono::NaiveDate::parse_from_str(trimmed, "%Y-%m-%d")
+                .map(|date| Value::Text(date.format("%Y-%m-%d").to_string()))
+                .map_err(|_| anyhow!("'{}' is not a date (expected YYYY-MM-DD)", raw)),
+            ParamType::Boolean => match trimmed.to_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok(Value::Integer(1)),
+                "false" | "no" | "0" => Ok(Value::Integer(0)),
+                _ => bail!("'{}' is not a boolean (expected true or false)", raw),
+            },
+        }
+    }
+}
+
+/// Parses a parameter declaration: `name`, `name:type` or `name:type=default`
+pub fn parse_param_spec(raw: &str) -> Result<ParamSpec> {
+    let (declaration, default) = match raw.split_once('=') {
+        Some((declaration, default)) => (declaration, Some(default.to_string())),
+        None => (raw, None),
+    };
+    // The placeholder's own prefix comes off first, so `:since:date` is the name `since` of type date
+    let declaration = declaration.trim().trim_start_matches([':', '@', '$']);
+    let (name, kind) = match declaration.split_once(':') {
+        Some((name, kind)) => (name.trim(), kind.trim()),
+        None => (declaration, "text"),
+    };
+    if name.is_empty() {
+        bail!("Parameter name is empty in '{}'", raw);
+    }
+    let kind = ParamType::ALL
+        .into_iter()
+        .find(|t| t.name().eq_ignore_ascii_case(kind))
+        .ok_or_else(|| {
+            let names: Vec<&str> = ParamType::ALL.iter().map(|t| t.name()).collect();
+            anyhow!("Unknown parameter type '{}' (expected one of: {})", kind, names.join(", "))
+        })?;
+    if let Some(default) = &default {
+        kind.parse(default).with_context(|| format!("Invalid default for parameter '{}'", name))?;
+    }
+    Ok(ParamSpec { name: name.to_string(), kind, default })
+}
+
+/// Parses a `key=value` pair for a saved query parameter; the value is typed later
+pub fn parse_setting(raw: &str) -> Result<(String, String)> {
+    let (name, value) = raw
+        .split_once('=')
+        .ok_or_else(|| anyhow!("Expected name=value, got '{}'", raw))?;
+    let name = name.trim().trim_start_matches([':', '@', '$']);
+    if name.is_empty() {
+        bail!("Parameter name is empty in '{}'", raw);
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Names of the `:name`, `@name` and `$name` placeholders in SQL text, without their prefix, in order of first use.
+///
+/// Placeholders inside string literals, quoted identifiers and comments are not counted.
+pub fn named_placeholders(sql: &str) -> Vec<String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut names: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if let Some(close) = closing_quote(c) {
+            i += 1;
+            while i < chars.len() {
+                if chars[i] == close {
+                    // Doubled quotes are escapes, except inside brackets
+                    if close != ']' && chars.get(i + 1) == Some(&close) {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                i += 1;
+            }
+            i += 1;
+        } else if c == ':' && chars.get(i + 1) == Some(&':') {
+            // A `::` cast, as in other SQL dialects, is not a placeholder
+            i += 2;
+        } else if matches!(c, ':' | '@' | '$') {
+            let start = i + 1;
+            let mut end = start;
+            while end < chars.len() && (chars[end].is_alphanumeric() || chars[end] == '_') {
+                end += 1;
+            }
+            let name: String = chars[start..end].iter().collect();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_value_types() {
+        assert_eq!(parse_value("NULL").unwrap(), Value::Null);
+        assert_eq!(parse_value("-7").unwrap(), Value::Integer(-7));
+        assert_eq!(parse_value("2.5").unwrap(), Value::Real(2.5));
+        assert_eq!(parse_value("inf").unwrap(), Value::Text("inf".into()));
+        assert_eq!(parse_value("@@home").unwrap(), Value::Text("@home".into()));
+    }
+
+    #[test]
+    fn leading_zeros_stay_text() {
+        for raw in ["007", "-01", "00.5", "0123456789"] {
+            assert_eq!(parse_value(raw).unwrap(), Value::Text(raw.into()));
+        }
+        assert_eq!(parse_value("0").unwrap(), Value::Integer(0));
+        assert_eq!(parse_value("-0").unwrap(), Value::Integer(0));
+        assert_eq!(parse_value("0.5").unwrap(), Value::Real(0.5));
+        assert_eq!(parse_value("0e3").unwrap(), Value::Real(0.0));
+    }
+
+    #[test]
+    fn parses_named_values() {
+        assert_eq!(parse_named("n=42").unwrap(), ("n".to_string(), Value::Integer(42)));
+        assert_eq!(parse_named(":n=x=y").unwrap(), (":n".to_string(), Value::Text("x=y".into())));
+        assert!(parse_named("=42").is_err());
+    }
+
+    #[test]
+    fn finds_named_placeholders_in_order() {
+        assert_eq!(
+            named_placeholders("SELECT * FROM t WHERE a = :a AND b = @b OR c = $c AND d = :a"),
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn ignores_placeholders_in_quotes_and_comments() {
+        let sql = "SELECT ':no', \":no\", [:no], `:no` -- :no\n/* :no */ FROM t WHERE x = :yes AND y = 'it''s :no'";
+        assert_eq!(named_placeholders(sql), ["yes"]);
+    }
+
+    #[test]
+    fn casts_are_not_placeholders() {
+        assert_eq!(named_placeholders("SELECT x::int, :n::text FROM t"), ["n"]);
+    }
+
+    #[test]
+    fn bare_prefixes_are_not_placeholders() {
+        assert!(named_placeholders("SELECT ?1, ?, 'a' || : || @ FROM t").is_empty());
+    }
+
+    #[test]
+    fn parses_param_specs() {
+        assert_eq!(parse_param_spec("start").unwrap(), ParamSpec::new("start"));
+        let spec = parse_param_spec(" :since : date=2024-01-31").unwrap();
+        assert_eq!((spec.name.as_str(), spec.kind, spec.default.as_deref()), ("since", ParamType::Date, Some("2024-01-31")));
+        assert_eq!(parse_param_spec("n:INTEGER=5").unwrap().kind, ParamType::Integer);
+        // Only the first `=` separates the default
+        assert_eq!(parse_param_spec("s:text=a=b").unwrap().default.as_deref(), Some("a=b"));
+    }
+
+    #[test]
+    fn rejects_malformed_param_specs() {
+        for spec in ["", ":", "=5", "n:", "n:number", "n:integer=five", "d:date=2024-02-30", "b:boolean=maybe"] {
+            assert!(parse_param_spec(spec).is_err(), "'{}' was accepted", spec);
+        }
+    }
+
+    #[test]
+    fn parses_settings() {
+        assert_eq!(parse_setting("@x= 1 ").unwrap(), ("x".to_string(), " 1 ".to_string()));
+        assert!(parse_setting("x").is_err());
+        assert!(parse_setting("=1").is_err());
+    }
+
+    fn bind_all(sql: &[&str], params: &QueryParams) -> Result<Vec<Vec<Value>>> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        let mut binder = ParamBinder::new(params, sql.len());
+        let mut results = Vec::new();
+        for sql in sql {
+            let mut stmt = conn.prepare(sql)?;
+            binder.bind(&mut stmt)?;
+            let columns = stmt.column_count();
+            let mut rows = stmt.raw_query();
+            let row = rows.next()?.ok_or_else(|| anyhow!("no row"))?;
+            results.push((0..columns).map(|i| row.get::<_, Value>(i)).collect::<rusqlite::Result<_>>()?);
+        }
+        binder.finish()?;
+        Ok(results)
+    }
+
+    #[test]
+    fn later_statements_take_the_next_positional_values() {
+        let params = QueryParams {
+            positional: vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)],
+            named: vec![("n".into(), Value::Text("x".into()))],
+        };
+        let values = bind_all(&["SELECT ?1, :n", "SELECT ?1, ?2, :n"], &params).unwrap();
+        assert_eq!(values[0], [Value::Integer(1), Value::Text("x".into())]);
+        assert_eq!(values[1], [Value::Integer(2), Value::Integer(3), Value::Text("x".into())]);
+    }
+
+    #[test]
+    fn placeholders_without_values_are_errors() {
+        let one = QueryParams { positional: vec![Value::Integer(1)], named: Vec::new() };
+        assert!(bind_all(&["SELECT ?1, ?2"], &one).is_err());
+        assert!(bind_all(&["SELECT ?1", "SELECT ?"], &one).is_err());
+        assert!(bind_all(&["SELECT ?1, :missing"], &one).is_err());
+        // Unused values are errors too
+        assert!(bind_all(&["SELECT 1"], &one).is_err());
+        assert!(bind_all(&["SELECT ?"], &one).is_ok());
+    }
+}
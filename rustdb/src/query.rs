@@ -1,113 +1,239 @@
-use rusqlite::{Connection, Result};
-use comfy_table::{Table, presets::UTF8_FULL};
-use crate::export::{export_to_csv, export_to_json, save_benchmark_log};
-use serde::Serialize;
-use anyhow::Result as AnyResult;
-
+use rusqlite::Connection;
+use comfy_table::Table;
+use crate::export::{create_output_file, export_to_file, save_benchmark_log};
+use crate::output::{FormatSettings, OutputFormat, RowWriter, write_rows, writer_for};
+use crate::params::{ParamBinder, QueryParams};
+use crate::script::{leading_keyword, split_statements};
+use anyhow::{Result as AnyResult, bail};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+
+/// Options controlling how a query is run and where its results go
+pub struct QueryOptions<'a> {
+    pub format: OutputFormat,
+    pub output: Option<&'a str>,
+    pub csv_file: Option<&'a str>,
+    pub json_file: Option<&'a str>,
+    pub settings: FormatSettings,
+    pub explain: bool,
+    pub profile: bool,
+    pub params: QueryParams,
+    pub continue_on_error: bool,
+}
 
-#[derive(Serialize)]
-struct RowRecord {
-    values: Vec<String>,
+impl QueryOptions<'_> {
+    /// Prints a status line, keeping stdout clean when results are piped from it
+    fn notice(&self, message: &str) {
+        if self.format != OutputFormat::Table && self.output.is_none() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
 }
 
-/// Runs a SQL query and prints results in a formatted table, optionally export CSV/JSON
-pub fn run_query(db_path: &str, sql: &str, csv_file: Option<&str>, json_file: Option<&str>, explain: bool, profile: bool) -> AnyResult<()> {
+/// Runs SQL text and prints results in a formatted table, optionally export CSV/JSON.
+///
+/// The text may hold several statements; they run in order, each
+/// row-returning statement gets its own table and DML reports rows affected.
+pub fn run_query(db_path: &str, sql: &str, options: &QueryOptions) -> AnyResult<()> {
     let conn = Connection::open(db_path)?;
 
-    if explain {
-        // Run explain query plan
-        let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
-        let rows_iter = stmt.query_map([], |row| {
-            Ok((
-                row.get::<_, i32>(0)?, // id
-                row.get::<_, i32>(1)?, // parent
-                row.get::<_, i32>(2)?, // notused
-                row.get::<_, String>(3)?, // detail
-            ))
-        })?;
-
-        let mut table = Table::new();
-        table.set_header(vec!["id", "parent", "notused", "detail"]);
-
-        for row in rows_iter {
-            let (id, parent, notused, detail) = row?;
-            table.add_row(vec![id.to_string(), parent.to_string(), notused.to_string(), detail.to_string()]);
-        }
-
-        println!("{table}");
-        return Ok(()); // Skip normal query execution
+    let statements = split_statements(sql);
+    if statements.is_empty() {
+        bail!("No SQL statements to run");
     }
+    let script = statements.len() > 1;
 
     // Start profile query plan
-    let start_time = if profile {
+    let start_time = if options.profile && !options.explain {
         Some(std::time::Instant::now())
     } else {
         None
     };
 
-    let mut stmt = conn.prepare(sql)?;
-
-    let column_names: Vec<String> = stmt.column_names().iter().map(|s| s.to_string()).collect();
-    let column_count = stmt.column_count();
-
-    // Map rows
-    let rows_iter = stmt.query_map([], |row| {
-        Ok(RowRecord {
-            values: row_to_strings(row, column_count),
-        })
-    })?;
-
-    // Collect rows into Vec
-    let rows: Vec<RowRecord> = rows_iter.collect::<Result<_, rusqlite::Error>>()?;
-
-    // Print to console
-    let mut table = Table::new();
-    table.load_preset(UTF8_FULL);
-    table.set_header(column_names.clone());
+    let mut binder = ParamBinder::new(&options.params, statements.len());
+    let mut files = OutputFiles { script, ..OutputFiles::default() };
+    let mut failures = 0;
+    for (n, statement) in statements.iter().enumerate() {
+        if script {
+            options.notice(&format!("-- Statement {} (line {})", n + 1, statement.line));
+        }
 
-    for row in &rows {
-        table.add_row(row.values.clone());
+        let result = if options.explain {
+            explain_statement(&conn, &statement.sql, &mut binder)
+        } else {
+            run_statement(&conn, &statement.sql, options, &mut binder, &mut files)
+        };
+
+        if let Err(e) = result {
+            if !script {
+                return Err(e);
+            }
+            let e = e.context(format!("Statement {} (line {}) failed", n + 1, statement.line));
+            if !options.continue_on_error {
+                files.finish(options)?;
+                return Err(e);
+            }
+            eprintln!("Error: {:#}", e);
+            failures += 1;
+        }
     }
 
-    println!("{table}");
-
-    // Export CSV if requested
-    if let Some(file) = csv_file {
-        let headers: Vec<&str> = column_names.iter().map(|s| s.as_str()).collect();
-        let row_values: Vec<&[String]> = rows.iter().map(|r| r.values.as_slice()).collect();
-        export_to_csv(file, &headers, &row_values)?;
-        println!("Exported to CSV: {}", file);
-    }
+    files.finish(options)?;
 
-    // Export JSON if requested
-    if let Some(file) = json_file {
-        export_to_json(file, &rows)?;
-        println!("Exported to JSON: {}", file);
+    // Values a script did not use; left out after failures, whose statements may not have been bound
+    if script && failures == 0 {
+        binder.finish()?;
     }
 
     // End profile query
     if let Some(start) = start_time {
         let elapsed = start.elapsed();
-        println!(
+        options.notice(&format!(
             "Query executed in: {:.4} ms",
             elapsed.as_secs_f64() * 1000.0
-        );
+        ));
         // Writes timing to benchmark.json
         save_benchmark_log(sql, elapsed)?;
     }
 
+    if failures > 0 {
+        bail!("{} of {} statements failed", failures, statements.len());
+    }
+
+    Ok(())
+}
+
+/// Prints SQLite's query plan for a single statement
+fn explain_statement(conn: &Connection, sql: &str, binder: &mut ParamBinder) -> AnyResult<()> {
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql))?;
+    binder.bind(&mut stmt)?;
+    let mut rows = stmt.raw_query();
+
+    let mut table = Table::new();
+    table.set_header(vec!["id", "parent", "notused", "detail"]);
+
+    while let Some(row) = rows.next()? {
+        let id = row.get::<_, i32>(0)?;
+        let parent = row.get::<_, i32>(1)?;
+        let notused = row.get::<_, i32>(2)?;
+        let detail = row.get::<_, String>(3)?;
+        table.add_row(vec![id.to_string(), parent.to_string(), notused.to_string(), detail]);
+    }
+
+    println!("{table}");
     Ok(())
 }
 
-/// Converts all types to String properly
-fn row_to_strings(row: &rusqlite::Row, column_count: usize) -> Vec<String> {
-    (0..column_count)
-        .map(|i| match row.get_ref(i) {
-            Ok(rusqlite::types::ValueRef::Integer(v)) => v.to_string(),
-            Ok(rusqlite::types::ValueRef::Real(v)) => v.to_string(),
-            Ok(rusqlite::types::ValueRef::Text(t)) => String::from_utf8_lossy(t).to_string(),
-            Ok(rusqlite::types::ValueRef::Blob(_)) => "<BLOB>".to_string(),
-            Ok(rusqlite::types::ValueRef::Null) | Err(_) => "NULL".to_string(),
-        })
-        .collect()
-}
\ No newline at end of file
+/// Runs a single statement, writing its rows as they are read or printing the number of rows it changed
+fn run_statement(
+    conn: &Connection,
+    sql: &str,
+    options: &QueryOptions,
+    binder: &mut ParamBinder,
+    files: &mut OutputFiles,
+) -> AnyResult<()> {
+    let mut stmt = conn.prepare(sql)?;
+    binder.bind(&mut stmt)?;
+
+    let column_count = stmt.column_count();
+    if column_count == 0 {
+        let changed = stmt.raw_execute()?;
+        if is_dml(sql) {
+            options.notice(&format!("{} row(s) affected", changed));
+        } else {
+            options.notice("OK");
+        }
+        return Ok(());
+    }
+
+    files.start_result_set(options)?;
+
+    // Main output plus any requested exports, all fed from the same cursor
+    let mut writers: Vec<Box<dyn RowWriter>> = Vec::new();
+    match options.output {
+        Some(path) => writers.push(export_to_file(open_once(&mut files.output, path)?, options.format, &options.settings)?),
+        None => writers.push(writer_for(options.format, BufWriter::new(io::stdout()), &options.settings)),
+    }
+    if let Some(path) = options.csv_file {
+        writers.push(export_to_file(open_once(&mut files.csv, path)?, OutputFormat::Csv, &options.settings)?);
+    }
+    if let Some(path) = options.json_file {
+        writers.push(export_to_file(open_once(&mut files.json, path)?, OutputFormat::Json, &options.settings)?);
+    }
+
+    let mut writer_refs: Vec<&mut (dyn RowWriter + 'static)> = writers.iter_mut().map(|w| w.as_mut()).collect();
+    write_rows(&mut stmt, &mut writer_refs)?;
+
+    if let Some(file) = options.output {
+        options.notice(&format!("Wrote results to: {}", file));
+    }
+    if let Some(file) = options.csv_file {
+        options.notice(&format!("Exported to CSV: {}", file));
+    }
+    if let Some(file) = options.json_file {
+        options.notice(&format!("Exported to JSON: {}", file));
+    }
+
+    Ok(())
+}
+
+/// Files named by `--output`, `--csv` and `--json`, created for the first result set and shared by the rest
+#[derive(Default)]
+struct OutputFiles {
+    output: Option<File>,
+    csv: Option<File>,
+    json: Option<File>,
+    /// Row-returning statements written so far
+    result_sets: usize,
+    /// Whether the SQL has several statements, so JSON gets an outer array with one array per result set
+    script: bool,
+}
+
+impl OutputFiles {
+    /// Opens or continues the outer JSON array of a script before its next result set
+    fn start_result_set(&mut self, options: &QueryOptions) -> AnyResult<()> {
+        if self.script {
+            self.write_json(options, if self.result_sets == 0 { "[\n" } else { ",\n" })?;
+        }
+        self.result_sets += 1;
+        Ok(())
+    }
+
+    /// Closes the outer JSON array of a script, if one was started
+    fn finish(&mut self, options: &QueryOptions) -> AnyResult<()> {
+        if self.script && self.result_sets > 0 {
+            self.write_json(options, "]\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes text to every destination in JSON format
+    fn write_json(&mut self, options: &QueryOptions, text: &str) -> AnyResult<()> {
+        if options.format == OutputFormat::Json {
+            match options.output {
+                Some(path) => open_once(&mut self.output, path)?.write_all(text.as_bytes())?,
+                None => io::stdout().write_all(text.as_bytes())?,
+            }
+        }
+        if let Some(path) = options.json_file {
+            open_once(&mut self.json, path)?.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+/// The file already opened for a destination, or a newly created one
+fn open_once<'a>(file: &'a mut Option<File>, path: &str) -> AnyResult<&'a File> {
+    Ok(match file {
+        Some(file) => file,
+        None => file.insert(create_output_file(path)?),
+    })
+}
+
+/// Whether a statement modifies rows, so its change count is meaningful
+pub fn is_dml(sql: &str) -> bool {
+    matches!(leading_keyword(sql).as_str(), "INSERT" | "UPDATE" | "DELETE" | "REPLACE" | "WITH")
+}
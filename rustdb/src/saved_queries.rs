@@ -1,32 +1,513 @@
+use anyhow::{Context, Result, anyhow, bail};
+use clap::ValueEnum;
+use comfy_table::Table;
+use rusqlite::types::Value;
 use serde::{Serialize, Deserialize};
-use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+use crate::config::config_dir;
+use crate::history::{database_key, local_time, one_line};
+use crate::params::{ParamSpec, named_placeholders};
+use crate::storage::JsonList;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SavedQuery {
     pub name: String,
     pub sql: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Named placeholders in `sql`, with their types and defaults
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<ParamSpec>,
+    /// RFC 3339 timestamps; queries saved by older versions have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+
+impl SavedQuery {
+    /// A new query whose placeholders are declared as text without defaults
+    pub fn new(name: &str, sql: &str) -> Self {
+        let now = chrono::Utc::now().to_rfc3339();
+        SavedQuery {
+            name: name.trim().to_string(),
+            sql: sql.to_string(),
+            description: None,
+            tags: Vec::new(),
+            params: named_placeholders(sql).iter().map(|name| ParamSpec::new(name)).collect(),
+            created: Some(now.clone()),
+            updated: Some(now),
+        }
+    }
+
+    /// Values to bind for the declared parameters: those given in `settings`, otherwise the defaults
+    pub fn bind_values(&self, settings: &[(String, String)]) -> Result<Vec<(String, Value)>> {
+        for (name, _) in settings {
+            if !self.params.iter().any(|p| p.name == *name) {
+                bail!("Saved query '{}' has no parameter '{}'", self.name, name);
+            }
+        }
+        self.params
+            .iter()
+            .map(|param| {
+                let raw = settings
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| *name == param.name)
+                    .map(|(_, value)| value)
+                    .or(param.default.as_ref())
+                    .ok_or_else(|| anyhow!("No value for parameter '{}' (use --set {}=...)", param.name, param.name))?;
+                let value = param.kind.parse(raw).with_context(|| format!("Invalid value for parameter '{}'", param.name))?;
+                Ok((param.name.clone(), value))
+            })
+            .collect()
+    }
+
+    fn touch(&mut self) {
+        self.updated = Some(chrono::Utc::now().to_rfc3339());
+    }
+}
+
+/// Changes to apply with `update_query`; `None` leaves a field as it is
+#[derive(Default)]
+pub struct QueryChanges {
+    pub sql: Option<String>,
+    /// An empty description removes it
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// Declarations to add or replace, by name
+    pub params: Option<Vec<ParamSpec>>,
+}
+
+/// Declarations for every placeholder in `sql`: from `declared` where given, otherwise text without a default
+pub fn declare_params(sql: &str, declared: &[ParamSpec]) -> Result<Vec<ParamSpec>> {
+    let placeholders = named_placeholders(sql);
+    if let Some(unused) = declared.iter().find(|p| !placeholders.contains(&p.name)) {
+        bail!("Parameter '{}' does not appear in the SQL", unused.name);
+    }
+    Ok(placeholders
+        .iter()
+        .map(|name| declared.iter().find(|p| p.name == *name).cloned().unwrap_or_else(|| ParamSpec::new(name)))
+        .collect())
+}
+
+/// Environment variable naming a saved queries file to use instead of the usual stores
+pub const SAVED_QUERIES_ENV: &str = "RUSTDB_SAVED_QUERIES";
+
+const FILE_NAME: &str = "saved_queries.json";
+
+/// Version of the saved queries file format; files without one are from before it was versioned
+const FORMAT_VERSION: u64 = 1;
+
+/// Directory holding a project's store, found by walking up from the working directory
+const PROJECT_DIR: &str = ".rustdb";
+
+/// Which store a saved query lives in; a query hides same-named ones in the scopes after it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Only offered for one database
+    Database,
+    /// Shared by a directory tree, e.g. a repository
+    Project,
+    /// Offered everywhere
+    Global,
+    /// The file given with `--saved-queries` or `$RUSTDB_SAVED_QUERIES`
+    #[value(skip)]
+    File,
+}
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Scope::Database => "database",
+            Scope::Project => "project",
+            Scope::Global => "global",
+            Scope::File => "file",
+        }
+    }
+}
+
+/// A saved queries file and the scope it serves
+#[derive(Clone, Debug)]
+pub struct Store {
+    pub scope: Scope,
+    pub path: PathBuf,
+}
+
+/// A saved query and the scope it was found in
+#[derive(Serialize, Clone, Debug)]
+pub struct ScopedQuery {
+    pub scope: Scope,
+    #[serde(flatten)]
+    pub query: SavedQuery,
+    /// A query with the same name in a more specific scope is used instead
+    #[serde(skip)]
+    pub shadowed: bool,
+}
+
+/// The saved query stores in use, most specific first
+#[derive(Clone, Debug)]
+pub struct SavedStores {
+    stores: Vec<Store>,
+}
+
+impl SavedStores {
+    /// The database (when `db_path` is given), project and global stores, or only `file` (or
+    /// `$RUSTDB_SAVED_QUERIES`) when that is set
+    pub fn locate(file: Option<&str>, db_path: Option<&str>) -> Self {
+        let file = file.map(PathBuf::from).or_else(|| std::env::var_os(SAVED_QUERIES_ENV).map(PathBuf::from));
+        if let Some(path) = file {
+            return SavedStores { stores: vec![Store { scope: Scope::File, path }] };
+        }
+        let mut stores = Vec::new();
+        if let Some(key) = db_path.and_then(database_key)
+            && let Some(dir) = config_dir()
+        {
+            stores.push(Store { scope: Scope::Database, path: dir.join("saved_queries").join(format!("{}.json", key)) });
+        }
+        if let Some(path) = project_store() {
+            stores.push(Store { scope: Scope::Project, path });
+        }
+        if let Some(dir) = config_dir() {
+            stores.push(Store { scope: Scope::Global, path: dir.join(FILE_NAME) });
+        }
+        SavedStores { stores }
+    }
+
+    pub fn stores(&self) -> &[Store] {
+        &self.stores
+    }
+
+    /// Where new queries go unless told otherwise: the project store when there is one, else the global store
+    pub fn default_scope(&self) -> Scope {
+        let exists = |scope| self.stores.iter().any(|s| s.scope == scope && s.path.exists());
+        let present = |scope| self.stores.iter().any(|s| s.scope == scope);
+        if present(Scope::File) {
+            Scope::File
+        } else if exists(Scope::Project) || !present(Scope::Global) {
+            Scope::Project
+        } else {
+            Scope::Global
+        }
+    }
+
+    pub fn store(&self, scope: Scope) -> Result<&Store> {
+        self.stores.iter().find(|s| s.scope == scope).ok_or_else(|| match scope {
+            _ if self.stores.iter().any(|s| s.scope == Scope::File) => {
+                anyhow!("Only the --saved-queries file is in use, so there is no {} scope", scope.name())
+            }
+            Scope::Database => anyhow!("The database scope needs a database (use --db <path>)"),
+            _ => anyhow!("There is no {} saved queries file here", scope.name()),
+        })
+    }
+
+    /// Every saved query, most specific scope first
+    pub fn load(&self) -> Result<Vec<ScopedQuery>> {
+        let mut queries: Vec<ScopedQuery> = Vec::new();
+        for store in &self.stores {
+            for query in load_saved_queries(&store.path)? {
+                let shadowed = queries.iter().any(|q| q.query.name == query.name);
+                queries.push(ScopedQuery { scope: store.scope, query, shadowed });
+            }
+        }
+        Ok(queries)
+    }
+
+    /// The query with this name in `scope`, or in the most specific scope that has one
+    pub fn find(&self, name: &str, scope: Option<Scope>) -> Result<ScopedQuery> {
+        let name = name.trim();
+        self.load()?
+            .into_iter()
+            .find(|q| q.query.name == name && scope.is_none_or(|scope| q.scope == scope))
+            .ok_or_else(|| match scope {
+                Some(scope) => anyhow!("No {} saved query named '{}'", scope.name(), name),
+                None => anyhow!("No saved query named '{}'", name),
+            })
+    }
+
+    pub fn exists(&self, name: &str, scope: Scope) -> Result<bool> {
+        Ok(load_saved_queries(&self.store(scope)?.path)?.iter().any(|q| q.name == name.trim()))
+    }
+
+    /// Saves a query in `scope`, or the default one, returning where it went
+    pub fn add(&self, query: SavedQuery, scope: Option<Scope>, overwrite: bool) -> Result<Scope> {
+        let store = self.store(scope.unwrap_or_else(|| self.default_scope()))?;
+        add_query(&store.path, query, overwrite)?;
+        Ok(store.scope)
+    }
+
+    pub fn update(&self, name: &str, scope: Option<Scope>, changes: QueryChanges) -> Result<Scope> {
+        let scope = self.find(name, scope)?.scope;
+        update_query(&self.store(scope)?.path, name, changes)?;
+        Ok(scope)
+    }
+
+    pub fn rename(&self, name: &str, scope: Option<Scope>, new_name: &str) -> Result<Scope> {
+        let scope = self.find(name, scope)?.scope;
+        rename_query(&self.store(scope)?.path, name, new_name)?;
+        Ok(scope)
+    }
+
+    pub fn delete(&self, name: &str, scope: Option<Scope>) -> Result<Scope> {
+        let scope = self.find(name, scope)?.scope;
+        delete_query(&self.store(scope)?.path, name)?;
+        Ok(scope)
+    }
+
+    /// Adds queries to `scope`, or the default one, in one change to its file; returns where they went
+    pub fn import(
+        &self,
+        imported: Vec<SavedQuery>,
+        scope: Option<Scope>,
+        on_conflict: OnConflict,
+    ) -> Result<(Scope, ImportReport)> {
+        let store = self.store(scope.unwrap_or_else(|| self.default_scope()))?;
+        saved_file(&store.path).update(|queries: &mut Vec<SavedQuery>| {
+            let mut report = ImportReport::default();
+            for mut query in imported {
+                let Some(i) = queries.iter().position(|q| q.name == query.name) else {
+                    report.added.push(query.name.clone());
+                    queries.push(query);
+                    continue;
+                };
+                match on_conflict {
+                    OnConflict::Skip => report.skipped.push(query.name),
+                    OnConflict::Overwrite => {
+                        report.overwritten.push(query.name.clone());
+                        queries[i] = query;
+                    }
+                    OnConflict::Rename => {
+                        let name = (2..)
+                            .map(|n| format!("{} ({})", query.name, n))
+                            .find(|name| !queries.iter().any(|q| q.name == *name))
+                            .unwrap_or_default();
+                        report.renamed.push((std::mem::replace(&mut query.name, name.clone()), name));
+                        queries.push(query);
+                    }
+                }
+            }
+            Ok((store.scope, report))
+        })
+    }
+}
+
+/// What `saved import` does with a query whose name is already taken
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum OnConflict {
+    /// Keep the existing query
+    Skip,
+    /// Replace the existing query
+    Overwrite,
+    /// Add the imported query as `name (2)`, `name (3)`, ...
+    Rename,
+}
+
+/// Names of the queries an import added, replaced, renamed (from, to) and left out
+#[derive(Default, Debug)]
+pub struct ImportReport {
+    pub added: Vec<String>,
+    pub overwritten: Vec<String>,
+    pub renamed: Vec<(String, String)>,
+    pub skipped: Vec<String>,
+}
+
+/// The nearest `.rustdb/saved_queries.json` at or above the working directory, or a `saved_queries.json`
+/// in it as older versions wrote. Without either, where a new one would go: the repository root, else here.
+fn project_store() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        let path = dir.join(PROJECT_DIR).join(FILE_NAME);
+        if path.exists() {
+            return Some(path);
+        }
+        if dir == cwd && dir.join(FILE_NAME).exists() {
+            return Some(dir.join(FILE_NAME));
+        }
+    }
+    let root = cwd.ancestors().find(|dir| dir.join(".git").exists()).unwrap_or(&cwd);
+    Some(root.join(PROJECT_DIR).join(FILE_NAME))
+}
+
+/// A saved queries file: `{"version": 1, "queries": [...]}`
+pub fn saved_file(path: &Path) -> JsonList {
+    JsonList::new(path, "queries", FORMAT_VERSION)
+}
+
+/// Load all saved queries from a file
+fn load_saved_queries(path: &Path) -> Result<Vec<SavedQuery>> {
+    saved_file(path).load()
+}
+
+/// Saves a query; one with the same name is replaced when `overwrite` is set and an error otherwise.
+///
+/// A replaced query keeps its creation time, its description and tags unless new ones are given,
+/// and the declarations of placeholders the new SQL leaves undeclared.
+fn add_query(path: &Path, mut query: SavedQuery, overwrite: bool) -> Result<()> {
+    if query.name.is_empty() {
+        bail!("Saved query names cannot be empty");
+    }
+    saved_file(path).update(|queries: &mut Vec<SavedQuery>| {
+        match queries.iter_mut().find(|q| q.name == query.name) {
+            Some(existing) if overwrite => {
+                query.created = existing.created.take();
+                if query.description.is_none() {
+                    query.description = existing.description.take();
+                }
+                if query.tags.is_empty() {
+                    query.tags = std::mem::take(&mut existing.tags);
+                }
+                for param in &mut query.params {
+                    if *param == ParamSpec::new(&param.name)
+                        && let Some(old) = existing.params.iter().find(|p| p.name == param.name)
+                    {
+                        *param = old.clone();
+                    }
+                }
+                *existing = query;
+                existing.touch();
+            }
+            Some(_) => bail!("A saved query named '{}' already exists", query.name),
+            None => queries.push(query),
+        }
+        Ok(())
+    })
+}
+
+fn update_query(path: &Path, name: &str, changes: QueryChanges) -> Result<()> {
+    if changes.sql.is_none() && changes.description.is_none() && changes.tags.is_none() && changes.params.is_none() {
+        bail!("Nothing to update; give new SQL, a description, tags or parameters");
+    }
+    saved_file(path).update(|queries: &mut Vec<SavedQuery>| {
+        let query = queries
+            .iter_mut()
+            .find(|q| q.name == name.trim())
+            .ok_or_else(|| anyhow!("No saved query named '{}'", name.trim()))?;
+        if changes.sql.is_some() || changes.params.is_some() {
+            // Declarations carry over to the new SQL for placeholders it still uses
+            let sql = changes.sql.unwrap_or_else(|| query.sql.clone());
+            let placeholders = named_placeholders(&sql);
+            let changed = changes.params.unwrap_or_default();
+            let mut declared: Vec<ParamSpec> = query
+                .params
+                .iter()
+                .filter(|p| placeholders.contains(&p.name) && !changed.iter().any(|c| c.name == p.name))
+                .cloned()
+                .collect();
+            declared.extend(changed);
+            query.params = declare_params(&sql, &declared)?;
+            query.sql = sql;
+        }
+        if let Some(description) = changes.description {
+            let description = description.trim();
+            query.description = if description.is_empty() { None } else { Some(description.to_string()) };
+        }
+        if let Some(tags) = changes.tags {
+            query.tags = tags;
+        }
+        query.touch();
+        Ok(())
+    })
+}
+
+fn rename_query(path: &Path, name: &str, new_name: &str) -> Result<()> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() {
+        bail!("Saved query names cannot be empty");
+    }
+    saved_file(path).update(|queries: &mut Vec<SavedQuery>| {
+        if new_name != name.trim() && queries.iter().any(|q| q.name == new_name) {
+            bail!("A saved query named '{}' already exists", new_name);
+        }
+        let query = queries
+            .iter_mut()
+            .find(|q| q.name == name.trim())
+            .ok_or_else(|| anyhow!("No saved query named '{}'", name.trim()))?;
+        query.name = new_name.to_string();
+        query.touch();
+        Ok(())
+    })
+}
+
+fn delete_query(path: &Path, name: &str) -> Result<()> {
+    saved_file(path).update(|queries: &mut Vec<SavedQuery>| {
+        let before = queries.len();
+        queries.retain(|q| q.name != name.trim());
+        if queries.len() == before {
+            bail!("No saved query named '{}'", name.trim());
+        }
+        Ok(())
+    })
+}
+
+/// Splits comma-separated tags, dropping empty ones
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    raw.split(',').map(str::trim).filter(|t| !t.is_empty()).map(String::from).collect()
 }
 
-const FILE_PATH: &str = "saved_queries.json";
+/// Prints saved queries, optionally only those with a tag or in one scope
+pub fn list_saved(stores: &SavedStores, tag: Option<&str>, scope: Option<Scope>, json: bool) -> Result<()> {
+    if let Some(scope) = scope {
+        stores.store(scope)?;
+    }
+    let queries: Vec<ScopedQuery> = stores
+        .load()?
+        .into_iter()
+        .filter(|q| scope.is_none_or(|scope| q.scope == scope))
+        .filter(|q| tag.is_none_or(|tag| q.query.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))))
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&queries)?);
+        return Ok(());
+    }
+    if queries.is_empty() {
+        println!("No saved queries.");
+        return Ok(());
+    }
 
-/// Load all saved queries from file
-pub fn load_saved_queries() -> Vec<SavedQuery> {
-    if !Path::new(FILE_PATH).exists() {
-        return Vec::new();
+    let mut table = Table::new();
+    table.set_header(vec!["Name", "Scope", "Description", "Tags", "Updated", "SQL"]);
+    for ScopedQuery { scope, query, shadowed } in &queries {
+        let scope = if *shadowed { format!("{} (shadowed)", scope.name()) } else { scope.name().to_string() };
+        table.add_row(vec![
+            query.name.clone(),
+            scope,
+            query.description.clone().unwrap_or_default(),
+            query.tags.join(", "),
+            query.updated.as_deref().map(local_time).unwrap_or_default(),
+            one_line(&query.sql, 60),
+        ]);
     }
-    let contents = fs::read_to_string(FILE_PATH).unwrap_or_default();
-    serde_json::from_str(&contents).unwrap_or_default()
+    println!("{table}");
+    Ok(())
 }
 
-/// Save all queries to file\
-pub fn save_new_query(name: &str, sql: &str) -> anyhow::Result<()> {
-    let mut queries = load_saved_queries();
-    queries.push(SavedQuery {
-        name: name.to_string(),
-        sql: sql.to_string(),
-    });
-    let contents = serde_json::to_string_pretty(&queries)?;
-    fs::write(FILE_PATH, contents)?;
+/// Prints one saved query in full
+pub fn show_saved(stores: &SavedStores, name: &str, scope: Option<Scope>) -> Result<()> {
+    let ScopedQuery { scope, query, .. } = stores.find(name, scope)?;
+    println!("Name: {}", query.name);
+    println!("Scope: {} ({})", scope.name(), stores.store(scope)?.path.display());
+    if let Some(description) = &query.description {
+        println!("Description: {}", description);
+    }
+    if !query.tags.is_empty() {
+        println!("Tags: {}", query.tags.join(", "));
+    }
+    if let Some(created) = &query.created {
+        println!("Created: {}", local_time(created));
+    }
+    if let Some(updated) = &query.updated {
+        println!("Updated: {}", local_time(updated));
+    }
+    if !query.params.is_empty() {
+        let params: Vec<String> = query.params.iter().map(|p| p.to_string()).collect();
+        println!("Parameters: {}", params.join(", "));
+    }
+    println!();
+    println!("{}", query.sql);
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,264 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, Read};
+
+/// One statement from a SQL script, with the line it starts on
+#[derive(Debug, Clone)]
+pub struct ScriptStatement {
+    pub sql: String,
+    pub line: usize,
+}
+
+/// Splits SQL text into individual statements.
+///
+/// Semicolons inside string literals, quoted identifiers, comments and
+/// `CREATE TRIGGER ... BEGIN ... END` bodies do not end a statement.
+/// Statements that are empty or only contain comments are dropped.
+pub fn split_statements(sql: &str) -> Vec<ScriptStatement> {
+    let mut statements = Vec::new();
+    let chars: Vec<char> = sql.chars().collect();
+
+    let mut current = String::new();
+    let mut start_line: Option<usize> = None;
+    let mut line = 1;
+    // Keywords seen at the top level of the current statement, used to spot triggers
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut block_depth = 0usize;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+
+        // Line comment
+        if c == '-' && chars.get(i + 1) == Some(&'-') {
+            while i < chars.len() && chars[i] != '\n' {
+                current.push(chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+
+        // Block comment
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            current.push_str("/*");
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                current.push(chars[i]);
+                i += 1;
+            }
+            if i < chars.len() {
+                current.push_str("*/");
+                i += 2;
+            }
+            continue;
+        }
+
+        if !c.is_whitespace() && c != ';' && start_line.is_none() {
+            start_line = Some(line);
+        }
+
+        // Quoted strings and identifiers; doubled quotes are escapes
+        if let Some(close) = closing_quote(c) {
+            current.push(c);
+            i += 1;
+            while i < chars.len() {
+                let q = chars[i];
+                if q == '\n' {
+                    line += 1;
+                }
+                current.push(q);
+                i += 1;
+                if q == close {
+                    if close != ']' && chars.get(i) == Some(&close) {
+                        current.push(close);
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+            finish_word(&mut word, &mut words, &mut block_depth);
+            continue;
+        }
+
+        if c.is_alphanumeric() || c == '_' {
+            word.push(c);
+        } else {
+            finish_word(&mut word, &mut words, &mut block_depth);
+        }
+
+        if c == ';' && block_depth == 0 {
+            if let Some(line) = start_line {
+                statements.push(ScriptStatement { sql: current.trim().to_string(), line });
+            }
+            current.clear();
+            words.clear();
+            start_line = None;
+        } else {
+            current.push(c);
+        }
+
+        if c == '\n' {
+            line += 1;
+        }
+        i += 1;
+    }
+
+    if let Some(line) = start_line {
+        statements.push(ScriptStatement { sql: current.trim().to_string(), line });
+    }
+
+    statements
+}
+
+/// Reads a SQL script from a file, or from stdin when the path is `-`
+pub fn read_script(path: &str) -> Result<String> {
+    if path == "-" {
+        let mut sql = String::new();
+        io::stdin().read_to_string(&mut sql).context("Failed to read SQL from stdin")?;
+        return Ok(sql);
+    }
+    fs::read_to_string(path).with_context(|| format!("Failed to read SQL file '{}'", path))
+}
+
+/// Skips whitespace and comments at the start of a statement
+pub fn strip_leading_comments(sql: &str) -> &str {
+    let mut rest = sql.trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix("--") {
+            rest = after.split_once('\n').map(|(_, tail)| tail).unwrap_or("").trim_start();
+        } else if let Some(after) = rest.strip_prefix("/*") {
+            rest = after.split_once("*/").map(|(_, tail)| tail).unwrap_or("").trim_start();
+        } else {
+            return rest;
+        }
+    }
+}
+
+/// The first keyword of a statement, uppercased, e.g. `SELECT`
+pub fn leading_keyword(sql: &str) -> String {
+    strip_leading_comments(sql)
+        .split(|c: char| !c.is_alphanumeric())
+        .find(|w| !w.is_empty())
+        .unwrap_or("")
+        .to_uppercase()
+}
+
+pub fn closing_quote(c: char) -> Option<char> {
+    match c {
+        '\'' => Some('\''),
+        '"' => Some('"'),
+        '`' => Some('`'),
+        '[' => Some(']'),
+        _ => None,
+    }
+}
+
+/// Records a finished keyword and tracks BEGIN/CASE ... END nesting inside triggers
+fn finish_word(word: &mut String, words: &mut Vec<String>, block_depth: &mut usize) {
+    if word.is_empty() {
+        return;
+    }
+    let upper = word.to_uppercase();
+    word.clear();
+
+    if is_trigger(words) {
+        match upper.as_str() {
+            "BEGIN" | "CASE" => *block_depth += 1,
+            "END" => *block_depth = block_depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    // Only the leading keywords are needed to recognise CREATE [TEMP] TRIGGER
+    if words.len() < 3 {
+        words.push(upper);
+    }
+}
+
+fn is_trigger(words: &[String]) -> bool {
+    match words {
+        [create, trigger, ..] if create == "CREATE" && trigger == "TRIGGER" => true,
+        [create, temp, trigger, ..] => {
+            create == "CREATE" && (temp == "TEMP" || temp == "TEMPORARY") && trigger == "TRIGGER"
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sql_of(text: &str) -> Vec<String> {
+        split_statements(text).into_iter().map(|s| s.sql).collect()
+    }
+
+    #[test]
+    fn splits_on_semicolons_and_records_lines() {
+        let statements = split_statements("SELECT 1;\n\nSELECT 2;\nSELECT 3");
+        let found: Vec<(&str, usize)> = statements.iter().map(|s| (s.sql.as_str(), s.line)).collect();
+        assert_eq!(found, [("SELECT 1", 1), ("SELECT 2", 3), ("SELECT 3", 4)]);
+    }
+
+    #[test]
+    fn keeps_semicolons_inside_quotes() {
+        assert_eq!(
+            sql_of(r#"SELECT 'a;b', "c;d", `e;f`, [g;h]; SELECT 'it''s; fine'"#),
+            [r#"SELECT 'a;b', "c;d", `e;f`, [g;h]"#, "SELECT 'it''s; fine'"]
+        );
+    }
+
+    #[test]
+    fn keeps_semicolons_inside_comments() {
+        assert_eq!(
+            sql_of("SELECT 1 -- one; two\n; SELECT /* a; b */ 2;"),
+            ["SELECT 1 -- one; two", "SELECT /* a; b */ 2"]
+        );
+    }
+
+    #[test]
+    fn drops_empty_and_comment_only_statements() {
+        assert_eq!(sql_of(";; -- nothing here\n; /* or here */ ;SELECT 1;;"), ["SELECT 1"]);
+        assert!(sql_of("  \n-- just a comment").is_empty());
+    }
+
+    #[test]
+    fn counts_lines_inside_comments_and_strings() {
+        let statements = split_statements("/* a\nb */ SELECT 'x\ny';\nSELECT 2;");
+        assert_eq!(statements[1].line, 4);
+    }
+
+    #[test]
+    fn keeps_trigger_bodies_together() {
+        let sql = "CREATE TRIGGER t AFTER INSERT ON a BEGIN\n  INSERT INTO b VALUES (1);\n  UPDATE b SET x = CASE WHEN x > 1 THEN 1 ELSE 0 END;\nEND;\nSELECT 1;";
+        let statements = sql_of(sql);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].starts_with("CREATE TRIGGER") && statements[0].ends_with("END"));
+        assert_eq!(statements[1], "SELECT 1");
+    }
+
+    #[test]
+    fn recognises_temporary_triggers() {
+        let statements = sql_of("CREATE TEMP TRIGGER t AFTER DELETE ON a BEGIN DELETE FROM b; END; SELECT 1");
+        assert_eq!(statements.len(), 2);
+    }
+
+    #[test]
+    fn begin_outside_a_trigger_does_not_nest() {
+        assert_eq!(
+            sql_of("BEGIN; INSERT INTO a VALUES (1); END; SELECT CASE WHEN 1 THEN 2 END;"),
+            ["BEGIN", "INSERT INTO a VALUES (1)", "END", "SELECT CASE WHEN 1 THEN 2 END"]
+        );
+    }
+
+    #[test]
+    fn strips_leading_comments() {
+        assert_eq!(strip_leading_comments("  -- a\n/* b */ INSERT INTO t"), "INSERT INTO t");
+        assert_eq!(strip_leading_comments("/* unterminated"), "");
+    }
+}
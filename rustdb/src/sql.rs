@@ -0,0 +1,94 @@
+use rusqlite::types::ValueRef;
+
+/// Quotes an identifier (table, column, schema) so it is safe to splice into SQL
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Splits a possibly schema-qualified name like `aux.users` or `"my schema"."my table"`.
+///
+/// Quoted parts may contain dots; quotes are removed from the returned parts.
+pub fn split_qualified_name(input: &str) -> (Option<String>, String) {
+    let mut parts: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut chars = input.trim().chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let close = match c {
+            '"' => Some('"'),
+            '`' => Some('`'),
+            '[' => Some(']'),
+            _ => None,
+        };
+        match close {
+            Some(close) if current.is_empty() => {
+                while let Some(q) = chars.next() {
+                    if q == close {
+                        // Doubled quotes are escapes, except inside brackets
+                        if close != ']' && chars.peek() == Some(&close) {
+                            chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    current.push(q);
+                }
+            }
+            _ if c == '.' && parts.is_empty() => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    parts.push(current);
+
+    match parts.len() {
+        2 => {
+            let name = parts.pop().unwrap_or_default();
+            (parts.pop(), name)
+        }
+        _ => (None, parts.pop().unwrap_or_default()),
+    }
+}
+
+/// Renders a value as a SQL literal
+pub fn quote_literal(value: &ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(i) => i.to_string(),
+        ValueRef::Real(r) if !r.is_finite() => "NULL".to_string(),
+        // Debug formatting always keeps a decimal point or exponent (`1.0`, `1e16`), so the value reads back as REAL
+        ValueRef::Real(r) => format!("{:?}", r),
+        ValueRef::Text(t) => format!("'{}'", String::from_utf8_lossy(t).replace('\'', "''")),
+        ValueRef::Blob(b) => format!("X'{}'", b.iter().map(|byte| format!("{:02X}", byte)).collect::<String>()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literals_read_back_as_the_same_type() {
+        assert_eq!(quote_literal(&ValueRef::Null), "NULL");
+        assert_eq!(quote_literal(&ValueRef::Integer(-42)), "-42");
+        assert_eq!(quote_literal(&ValueRef::Real(1.0)), "1.0");
+        assert_eq!(quote_literal(&ValueRef::Real(0.1)), "0.1");
+        // Large whole numbers keep an exponent rather than turning into INTEGER literals
+        assert_eq!(quote_literal(&ValueRef::Real(1e16)), "1e16");
+        assert_eq!(quote_literal(&ValueRef::Real(f64::INFINITY)), "NULL");
+        assert_eq!(quote_literal(&ValueRef::Real(f64::NAN)), "NULL");
+        assert_eq!(quote_literal(&ValueRef::Text(b"O'Brien")), "'O''Brien'");
+        assert_eq!(quote_literal(&ValueRef::Blob(&[0, 0xab, 0xff])), "X'00ABFF'");
+    }
+
+    #[test]
+    fn literals_round_trip_through_sqlite() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        for value in [ValueRef::Real(1e16), ValueRef::Real(-2.5e-300), ValueRef::Text(b"a'b\nc"), ValueRef::Blob(&[1, 2])] {
+            let sql = format!("SELECT {}, typeof({})", quote_literal(&value), quote_literal(&value));
+            let (read, kind): (rusqlite::types::Value, String) =
+                conn.query_row(&sql, [], |r| Ok((r.get(0)?, r.get(1)?))).unwrap();
+            assert_eq!(rusqlite::types::Value::from(value), read, "{}", sql);
+            assert_eq!(kind, value.data_type().to_string().to_lowercase());
+        }
+    }
+}
@@ -0,0 +1,34 @@
+/// Position of the `q` quantile (0 to 1) among `count` sorted values; a fractional rank falls
+/// between two values
+pub fn quantile_rank(q: f64, count: usize) -> f64 {
+    q * count.saturating_sub(1) as f64
+}
+
+/// Interpolates linearly between the values at the whole ranks either side of `rank`
+pub fn interpolate(rank: f64, value_at: impl Fn(usize) -> Option<f64>) -> Option<f64> {
+    let lower = rank.floor();
+    let low = value_at(lower as usize)?;
+    let high = value_at(rank.ceil() as usize)?;
+    Some(low + (high - low) * (rank - lower))
+}
+
+/// The `q` quantile of sorted values, as `analyze` and `bench` report them
+pub fn percentile(sorted: &[f64], q: f64) -> Option<f64> {
+    interpolate(quantile_rank(q, sorted.len()), |i| sorted.get(i).copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolates_between_ranks() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&values, 0.0), Some(1.0));
+        assert_eq!(percentile(&values, 0.5), Some(2.5));
+        assert_eq!(percentile(&values, 0.25), Some(1.75));
+        assert_eq!(percentile(&values, 1.0), Some(4.0));
+        assert_eq!(percentile(&[7.0], 0.99), Some(7.0));
+        assert_eq!(percentile(&[], 0.5), None);
+    }
+}
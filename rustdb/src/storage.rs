@@ -0,0 +1,230 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// A JSON file holding a list of entries, shared by every rustdb process that uses it.
+///
+/// The file is `{"version": N, "<key>": [...]}`. Changes are made under an exclusive lock on a
+/// `.lock` file beside it, written to a temporary file and renamed into place, so readers never see
+/// half a file and concurrent writers do not lose each other's entries. The previous contents are
+/// kept in a `.bak` file.
+pub struct JsonList {
+    path: PathBuf,
+    /// Name of the list in the file, e.g. `queries`
+    key: &'static str,
+    /// Format version written; files from older versions are read and upgraded on the next write
+    version: u64,
+}
+
+impl JsonList {
+    pub fn new(path: impl Into<PathBuf>, key: &'static str, version: u64) -> Self {
+        JsonList { path: path.into(), key, version }
+    }
+
+    /// Reads the entries; a missing file has none, but one that cannot be parsed is an error
+    pub fn load<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read '{}'", self.path.display()))?;
+        self.parse(&contents).with_context(|| {
+            let backup = with_suffix(&self.path, "bak");
+            if backup.exists() {
+                format!("Invalid file '{}' (the previous version is in '{}')", self.path.display(), backup.display())
+            } else {
+                format!("Invalid file '{}'", self.path.display())
+            }
+        })
+    }
+
+    /// Reads, changes and writes back the entries while holding the lock
+    pub fn update<T, R>(&self, change: impl FnOnce(&mut Vec<T>) -> Result<R>) -> Result<R>
+    where
+        T: Serialize + DeserializeOwned,
+    {
+        if let Some(dir) = self.path.parent()
+            && !dir.as_os_str().is_empty()
+        {
+            fs::create_dir_all(dir).with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        }
+        let lock = lock_file(&self.path)?;
+
+        let mut entries = self.load()?;
+        let result = change(&mut entries)?;
+        self.write(&entries)?;
+        // Dropping the file releases the lock
+        drop(lock);
+        Ok(result)
+    }
+
+    /// Writes the entries in this format without a lock or backup, for one-off files such as export bundles
+    pub fn export<T: Serialize>(&self, entries: &[T]) -> Result<()> {
+        fs::write(&self.path, self.document(entries)?)
+            .with_context(|| format!("Failed to write '{}'", self.path.display()))
+    }
+
+    fn parse<T: DeserializeOwned>(&self, contents: &str) -> Result<Vec<T>> {
+        let value: Value = serde_json::from_str(contents)?;
+        let entries = match value {
+            // Files from before the format was versioned are a bare list
+            Value::Array(_) => value,
+            Value::Object(mut fields) => {
+                let version = fields.get("version").and_then(Value::as_u64).unwrap_or(0);
+                if version > self.version {
+                    bail!(
+                        "written in format version {} by a newer rustdb, which only reads up to version {}",
+                        version,
+                        self.version
+                    );
+                }
+                // Loading a misnamed list as empty would lose it on the next write
+                match fields.remove(self.key) {
+                    Some(list @ Value::Array(_)) => list,
+                    Some(_) => bail!("\"{}\" is not a list", self.key),
+                    None => bail!("no \"{}\" list", self.key),
+                }
+            }
+            _ => bail!("expected an object with a \"{}\" list", self.key),
+        };
+        Ok(serde_json::from_value(entries)?)
+    }
+
+    /// Replaces the file with the entries via a temporary file, after copying the old one to `.bak`
+    fn write<T: Serialize>(&self, entries: &[T]) -> Result<()> {
+        let contents = self.document(entries)?;
+
+        if self.path.exists() {
+            let backup = with_suffix(&self.path, "bak");
+            fs::copy(&self.path, &backup).with_context(|| format!("Failed to back up to '{}'", backup.display()))?;
+        }
+        let temp = with_suffix(&self.path, "tmp");
+        let mut file = File::create(&temp).with_context(|| format!("Failed to create '{}'", temp.display()))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, &self.path).with_context(|| format!("Failed to replace '{}'", self.path.display()))?;
+        Ok(())
+    }
+
+    /// `{"version": N, "<key>": [...]}`
+    fn document<T: Serialize>(&self, entries: &[T]) -> Result<String> {
+        let mut fields = Map::new();
+        fields.insert("version".into(), self.version.into());
+        fields.insert(self.key.into(), serde_json::to_value(entries)?);
+        Ok(serde_json::to_string_pretty(&Value::Object(fields))?)
+    }
+}
+
+/// Waits for the exclusive lock on the `.lock` file beside `path`, held until the returned file is dropped
+pub fn lock_file(path: &Path) -> Result<File> {
+    let lock_path = with_suffix(path, "lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file '{}'", lock_path.display()))?;
+    lock.lock().with_context(|| format!("Failed to lock '{}'", lock_path.display()))?;
+    Ok(lock)
+}
+
+/// `saved_queries.json` becomes `saved_queries.json.<suffix>`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory for one test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustdb-storage-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_missing_versioned_and_legacy_files() {
+        let dir = test_dir("load");
+        let list = JsonList::new(dir.join("list.json"), "items", 2);
+        assert!(list.load::<String>().unwrap().is_empty());
+
+        fs::write(dir.join("list.json"), r#"{"version": 1, "items": ["a", "b"]}"#).unwrap();
+        assert_eq!(list.load::<String>().unwrap(), ["a", "b"]);
+        fs::write(dir.join("list.json"), r#"["c"]"#).unwrap();
+        assert_eq!(list.load::<String>().unwrap(), ["c"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn update_writes_the_list_and_keeps_a_backup() {
+        let dir = test_dir("update");
+        let path = dir.join("nested").join("list.json");
+        let list = JsonList::new(&path, "items", 1);
+        list.update(|items: &mut Vec<String>| {
+            items.push("a".into());
+            Ok(())
+        })
+        .unwrap();
+        assert!(!with_suffix(&path, "bak").exists());
+        let first = fs::read_to_string(&path).unwrap();
+        let document: Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(document, serde_json::json!({"version": 1, "items": ["a"]}));
+
+        let count = list.update(|items: &mut Vec<String>| {
+            items.push("b".into());
+            Ok(items.len())
+        });
+        assert_eq!(count.unwrap(), 2);
+        assert_eq!(list.load::<String>().unwrap(), ["a", "b"]);
+        assert_eq!(fs::read_to_string(with_suffix(&path, "bak")).unwrap(), first);
+        assert!(!with_suffix(&path, "tmp").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_change_leaves_the_file_alone() {
+        let dir = test_dir("failed");
+        let path = dir.join("list.json");
+        let list = JsonList::new(&path, "items", 1);
+        list.export(&["a"]).unwrap();
+        let before = fs::read_to_string(&path).unwrap();
+        assert!(list.update(|_: &mut Vec<String>| -> Result<()> { bail!("no") }).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), before);
+        assert!(!with_suffix(&path, "bak").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_newer_versions_and_missing_lists_without_writing() {
+        let dir = test_dir("reject");
+        let path = dir.join("list.json");
+        let list = JsonList::new(&path, "items", 1);
+        for contents in [
+            r#"{"version": 2, "items": []}"#,
+            r#"{"version": 1, "itmes": ["a"]}"#,
+            r#"{"version": 1, "items": {"a": 1}}"#,
+            r#""items""#,
+        ] {
+            fs::write(&path, contents).unwrap();
+            assert!(list.load::<String>().is_err(), "{} was accepted", contents);
+            let added = list.update(|items: &mut Vec<String>| {
+                items.push("b".into());
+                Ok(())
+            });
+            assert!(added.is_err(), "{} was overwritten", contents);
+            assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+            assert!(!with_suffix(&path, "bak").exists());
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
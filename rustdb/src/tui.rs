@@ -1,262 +1,660 @@
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers}, 
-    execute, 
-    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    event::{
+        self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+        KeyModifiers, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode, supports_keyboard_enhancement},
     cursor,
 };
 use ratatui::{
-    Terminal, 
-    backend::{CrosstermBackend}, 
-    layout::{Constraint, Direction, Layout}, 
+    Terminal,
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout},
+    style::{Color, Style},
     widgets::{Block, Borders, Paragraph},
     text::{Text, Line},
 };
-use rusqlite::{
-    Connection,
-    types::ValueRef,
-};
+use rusqlite::Connection;
 use std::{io};
+use std::sync::{Arc, Mutex};
+
+use crate::config::load_config;
+use crate::history::{History, HistoryEntry};
+use crate::params::QueryParams;
+use crate::saved_queries::{SavedQuery, SavedStores, Scope};
+
+mod complete;
+mod editor;
+mod form;
+mod keymap;
+mod results;
+mod saved;
+mod sidebar;
+mod worker;
 
-use crate::saved_queries::{SavedQuery, load_saved_queries, save_new_query};
+use complete::{SchemaCache, Suggestion, identifier, render_popup};
+use editor::Editor;
+use form::{FormKey, ParamForm};
+use keymap::{Action, Keymap};
+use results::ResultGrid;
+use saved::{SavedKey, SavedScreen};
+use sidebar::{Sidebar, SidebarAction, SidebarKey};
+use worker::{RunOutcome, RunningQuery, lock};
 
 enum InputMode {
-    SQL,
-    SaveName(String), // Holds current query to be named
-    SelectSaved(Vec<SavedQuery>), // Show saved queries for selection
+    Sql,
+    SaveName { sql: String, scope: Scope }, // Holds current query to be named and the store it goes to
+    ConfirmOverwrite { name: String, sql: String, scope: Scope }, // Saving under a name that is already taken
+    SelectSaved(SavedScreen), // Browse and manage saved queries
+    FillParams(ParamForm), // Values for a saved query's parameters
+    Search { query: String, found: Option<usize> }, // Reverse search through history
+}
+
+/// Which pane receives movement keys
+#[derive(PartialEq)]
+enum Focus {
+    Editor,
+    Results,
+    Sidebar,
 }
 
-const SQL_KEYWORDS: &[&str] = &[
-    "SELECT", "FROM", "WHERE", "INSERT", "UPDATE", "DELETE", "CREATE", "DROP", "ALTER",
-    "TABLE", "INDEX", "VIEW", "TRIGGER", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "ON",
-    "GROUP BY", "ORDER BY", "HAVING", "LIMIT", "OFFSET", "VALUES", "SET", "AND", "OR", "NOT",
-];
+/// Columns taken by the schema sidebar when it is shown
+const SIDEBAR_WIDTH: u16 = 32;
 
-pub fn start_tui(db_path: &str) -> anyhow::Result<()> {
-    let conn = Connection::open(db_path)?;
+pub fn start_tui(db_path: &str, stores: SavedStores) -> anyhow::Result<()> {
+    // Shared with the worker thread that runs queries
+    let conn = Arc::new(Mutex::new(Connection::open(db_path)?));
+    let keymap = Keymap::from_config(&load_config()?.keymap)?;
+    let mut history = History::open(db_path)?;
 
     // Enable raw mode and disable echo
     enable_raw_mode()?;
     let mut stdout = io::stdout();
 
-    execute!(stdout, EnterAlternateScreen, cursor::Hide)?;
+    execute!(stdout, EnterAlternateScreen, EnableBracketedPaste, cursor::Hide)?;
+
+    // Lets Shift+Enter and Ctrl+Enter be told apart from Enter where the terminal supports it
+    let enhanced_keys = supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced_keys {
+        execute!(stdout, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES))?;
+    }
 
     // Initialize Terminal
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut input = String::new();
-    let mut output: Vec<String> = vec!["Enter SQL query and press Enter.\nPress q to quit.".into()];
-    let mut input_mode = InputMode::SQL;
-    let mut suggestions: Vec<String> = Vec::new();
-    let mut number_buffer = String::new();
+    // Restore the terminal even when the loop fails
+    let result = run(&mut terminal, &conn, &keymap, &mut history, &stores);
+
+    // Cleanup
+    disable_raw_mode()?;
+
+    let mut stdout = io::stdout();
+    if enhanced_keys {
+        execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(stdout, LeaveAlternateScreen, DisableBracketedPaste, cursor::Show)?;
+
+    result
+}
+
+fn run<B: Backend>(
+    terminal: &mut Terminal<B>,
+    conn: &Arc<Mutex<Connection>>,
+    keymap: &Keymap,
+    history: &mut History,
+    stores: &SavedStores,
+) -> anyhow::Result<()> {
+    let mut editor = Editor::new();
+    let mut name_input = String::new();
+    let mut focus = Focus::Editor;
+    let mut output: Vec<String> = vec![
+        format!(
+            "Enter SQL query and press {} ({} for a new line).",
+            keymap.key_for(Action::Execute),
+            keymap.key_for(Action::NewLine)
+        ),
+        format!(
+            "{} switches between the editor and the results. Press {} to quit.",
+            keymap.key_for(Action::ToggleFocus),
+            keymap.key_for(Action::Quit)
+        ),
+        format!("{} shows the schema.", keymap.key_for(Action::ToggleSidebar)),
+    ];
+    output.extend(history.skipped_warning());
+    let mut input_mode = InputMode::Sql;
+    let mut schema = SchemaCache::load(&lock(conn))?;
+    let mut sidebar = Sidebar::load(&lock(conn))?;
+    let mut show_sidebar = false;
+    // First message line shown, and how many fit, for scrolling long output such as reports
+    let mut output_scroll = 0;
+    let mut output_page = 1;
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    // Characters before the cursor that a completion replaces
+    let mut completing = 0;
+    // Selected suggestion while the completion popup is open
+    let mut popup: Option<usize> = None;
+    let mut grid = ResultGrid::new();
+    // History entry being shown by Up/Down, and the text that was in the editor before
+    let mut recall: Option<(usize, String)> = None;
+    let mut running: Option<RunningQuery> = None;
+    // Messages from a query that finished while a prompt was using the output pane
+    let mut pending: Option<Vec<String>> = None;
+    // Parameter values from the form, bound again while the editor still holds their saved query
+    let mut bound: Option<(String, QueryParams)> = None;
 
     loop {
+        if let Some((result, elapsed)) = running.as_ref().and_then(RunningQuery::poll)
+            && let Some(query) = running.take()
+        {
+            let outcome = match &result {
+                Ok(RunOutcome::Rows(n, _) | RunOutcome::Changed(n)) => Ok(Some(*n)),
+                Ok(RunOutcome::Done | RunOutcome::Report(_)) => Ok(None),
+                Err(e) => Err(e.to_string()),
+            };
+            // Schema changes bring new names to complete and list
+            if matches!(result, Ok(RunOutcome::Done)) {
+                let conn = lock(conn);
+                if let Ok(reloaded) = SchemaCache::load(&conn) {
+                    schema = reloaded;
+                }
+                let _ = sidebar.reload(&conn);
+            }
+            let mut lines = match result {
+                Ok(RunOutcome::Rows(_, rows)) => {
+                    grid = rows;
+                    Vec::new()
+                }
+                Ok(RunOutcome::Changed(n)) => vec![format!("{} row(s) affected.", n)],
+                Ok(RunOutcome::Done) => vec!["OK".into()],
+                Ok(RunOutcome::Report(report)) => report.lines().map(String::from).collect(),
+                Err(_) if query.cancelled() => vec![format!("Query cancelled after {:.1}s.", elapsed.as_secs_f64())],
+                Err(e) if query.sql.is_none() => format!("Error: {e}").lines().map(String::from).collect(),
+                Err(e) => format!("SQL error: {e}").lines().map(String::from).collect(),
+            };
+            if let Some(sql) = &query.sql
+                && let Err(e) = history.record(HistoryEntry::new(sql, elapsed, outcome))
+            {
+                lines.push(format!("Could not save history: {e}"));
+            }
+            pending = Some(lines);
+        }
+        if matches!(input_mode, InputMode::Sql)
+            && let Some(lines) = pending.take()
+        {
+            output = lines;
+            output_scroll = 0;
+        }
+
+        let editor_height = editor.line_count().clamp(3, 10) as u16 + 2;
         terminal.draw(|f| {
-            let chunks = Layout::default()
+            let outer = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(3), // Input area
-                    Constraint::Length(5), // Suggestions area
-                    Constraint::Min(1) // Output area
+                    Constraint::Min(1), // Sidebar, editor and output
+                    Constraint::Length(1), // Status line
                 ])
                 .split(f.size());
-            
-            // Input block
-            let input_block = Paragraph::new(input.as_str())
-                    .block(Block::default().title("SQL Input").borders(Borders::ALL));
-            
-            // Suggestion block
-            let suggestion_text = Text::from(
-                suggestions.iter().take(5).map(|line| Line::from(line.as_str())).collect::<Vec<Line>>()
-            );
-            let suggestion_block = Paragraph::new(suggestion_text)
-                .block(Block::default().title("Suggestions").borders(Borders::ALL));
-
-            // Output block
-            let output_text = Text::from(
-                output.iter().map(|line| Line::from(line.as_str())).collect::<Vec<Line>>()
-            );
-
-            let output_block = Paragraph::new(output_text)
-                    .block(Block::default().title("Query Output").borders(Borders::ALL));
-
-            f.render_widget(input_block, chunks[0]);
-            f.render_widget(suggestion_block, chunks[1]);
-            f.render_widget(output_block, chunks[2]);
+            let (sidebar_area, main_area) = if show_sidebar {
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Length(SIDEBAR_WIDTH), Constraint::Min(1)])
+                    .split(outer[0]);
+                (Some(columns[0]), columns[1])
+            } else {
+                (None, outer[0])
+            };
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(editor_height), // Input area
+                    Constraint::Min(1), // Output area
+                ])
+                .split(main_area);
+
+            let focused = Style::default().fg(Color::Cyan);
+            let pane = |title: &'static str, has_focus: bool| {
+                let block = Block::default().title(title).borders(Borders::ALL);
+                if has_focus { block.border_style(focused) } else { block }
+            };
+            let editing = matches!(input_mode, InputMode::Sql) && focus == Focus::Editor;
+
+            editor.render(f, chunks[0], pane("SQL Input", editing), editing);
+
+            // Output block: messages when there are any, otherwise the last result set
+            let output_frame = pane("Query Output", focus == Focus::Results);
+            if let InputMode::SelectSaved(screen) = &mut input_mode {
+                screen.render(f, chunks[1], pane("Saved Queries", true));
+            } else if let InputMode::FillParams(form) = &input_mode {
+                form.render(f, chunks[1], pane("Parameters", true));
+            } else if output.is_empty() {
+                grid.render(f, chunks[1], output_frame);
+            } else {
+                output_page = (output_frame.inner(chunks[1]).height as usize).max(1);
+                output_scroll = output_scroll.min(output.len().saturating_sub(output_page));
+                let output_text = Text::from(
+                    output.iter().map(|line| Line::from(line.as_str())).collect::<Vec<Line>>()
+                );
+                let paragraph = Paragraph::new(output_text).block(output_frame).scroll((output_scroll as u16, 0));
+                f.render_widget(paragraph, chunks[1]);
+            }
+
+            if let Some(area) = sidebar_area {
+                sidebar.render(f, area, pane("Schema", focus == Focus::Sidebar));
+            }
+
+            let status = match &running {
+                Some(query) => query.status(&keymap.key_for(Action::Cancel)),
+                None => grid.status(),
+            };
+            f.render_widget(Paragraph::new(status), outer[1]);
+
+            // Completion popup, drawn last so it sits on top, starting under the word being completed
+            if let Some(selected) = popup {
+                let (x, y) = editor.screen_cursor();
+                render_popup(f, &suggestions, selected, (x.saturating_sub(completing as u16 + 1), y));
+            }
         })?;
 
         // Input handling
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(KeyEvent { code, kind: KeyEventKind::Press, modifiers, state: _ }) = event::read()? {
+        // Check back sooner while a query runs, to animate the spinner and pick up the result
+        let timeout = if running.is_some() { 25 } else { 100 };
+        if !event::poll(std::time::Duration::from_millis(timeout))? {
+            continue;
+        }
+        let (code, modifiers) = match event::read()? {
+            Event::Key(KeyEvent { code, kind: KeyEventKind::Press, modifiers, state: _ }) => (code, modifiers),
+            Event::Paste(text) => {
                 match &mut input_mode {
-                    InputMode::SQL => {
-                        match (code, modifiers) {
-                            (KeyCode::Char('q'), KeyModifiers::NONE) => break,
-                            (KeyCode::F(2), KeyModifiers::NONE) => {
-                                // Show saved queries
-                                let saved_queries = load_saved_queries();
-                                if saved_queries.is_empty() {
-                                    output = vec!["No saved queries.".into()];
-                                    input_mode = InputMode::SQL;
-                                } else {
-                                    output = saved_queries.iter().enumerate().map(|(i, q)| format!("{}: {}", i + 1, q.name)).collect();
-                                    number_buffer.clear();
-                                    input_mode = InputMode::SelectSaved(saved_queries);
-                                }
-                            }
-                            (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
-                                // Save current query
-                                if !input.trim().is_empty() {
-                                    input_mode = InputMode::SaveName(input.clone());
-                                    input.clear();
-                                    output = vec!["Enter a name for this query and press Enter:".into()];
-                                }
-                            }
-                            (KeyCode::Char(c), _) => input.push(c),
-                            (KeyCode::Backspace, _) => { input.pop(); }
-                            (KeyCode::Tab, _) => {
-                                // Autocomplete with first suggestion
-                                if !suggestions.is_empty() {
-                                    let first_suggestion = &suggestions[0];
-                                    
-                                    // IF it's a saved query, strip "Saved: " prefix
-                                    let autofill = if first_suggestion.starts_with("Saved: ") {
-                                        first_suggestion.trim_start_matches("Saved: ").to_string()
-                                    } else {
-                                        first_suggestion.clone()
-                                    };
-
-                                    input = autofill;
-                                    suggestions.clear();
-                                }
-                            }
-                            (KeyCode::Enter, _) => {
-                                if !input.trim().is_empty() {
-                                    // Prepare and run query
-                                    let query_result = match conn.prepare(&input) {
-                                        Ok(mut stmt) => {
-                                            let col_count = stmt.column_count();
-                                            match stmt.query_map([], move |row| {
-                                                let mut vals = Vec::new();
-                                                for i in 0..col_count {
-                                                    let v = row.get_ref(i)?;
-                                                    let s = match v {
-                                                        ValueRef::Null => "NULL".to_string(),
-                                                        ValueRef::Integer(i) => i.to_string(),
-                                                        ValueRef::Real(r) => r.to_string(),
-                                                        ValueRef::Text(t) => String::from_utf8_lossy(t).to_string(),
-                                                        ValueRef::Blob(_) => "<BLOB>".to_string(),
-                                                    };
-                                                    vals.push(s);
-                                                }
-
-                                                Ok(vals.join(" | "))
-                                            }) {
-                                                Ok(rows_iter) => {
-                                                    let rows: Vec<String> = rows_iter.filter_map(|r| r.ok()).collect();
-                                                    if rows.is_empty() { vec!["Query returned 0 rows.".into()] } else { rows }
-                                                }
-                                                Err(e) => vec![format!("SQL error: {e}")]
-                                            }
-                                        }
-                                        Err(e) => vec![format!("SQL error: {e}")]
-                                    };
-
-                                    output = query_result;
-                                    input.clear();
-                                }
-                            }
-                            _ => {}
+                    InputMode::Sql => {
+                        editor.paste(&text);
+                        focus = Focus::Editor;
+                        popup = None;
+                    }
+                    InputMode::SaveName { scope, .. } => {
+                        name_input.push_str(&text.replace(['\r', '\n'], " "));
+                        output = save_prompt(&name_input, stores, *scope);
+                    }
+                    InputMode::SelectSaved(screen) => screen.paste(&text),
+                    InputMode::FillParams(form) => form.paste(&text),
+                    InputMode::ConfirmOverwrite { .. } | InputMode::Search { .. } => {}
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        let action = keymap.action(code, modifiers);
+        if action == Some(Action::Quit) {
+            if let Some(query) = &mut running {
+                query.cancel();
+            }
+            break;
+        }
+
+        match &mut input_mode {
+            InputMode::Sql => {
+                // While the completion popup is open it takes the keys for moving through it
+                if let Some(selected) = popup {
+                    let count = suggestions.len();
+                    match code {
+                        _ if action == Some(Action::Complete) || code == KeyCode::Down => {
+                            popup = Some((selected + 1) % count);
+                            continue;
                         }
+                        KeyCode::BackTab | KeyCode::Up => {
+                            popup = Some((selected + count - 1) % count);
+                            continue;
+                        }
+                        KeyCode::Enter if modifiers.is_empty() => {
+                            editor.complete_word(completing, &suggestions[selected].text);
+                            popup = None;
+                            continue;
+                        }
+                        KeyCode::Esc => {
+                            popup = None;
+                            continue;
+                        }
+                        _ => {}
+                    }
+                }
 
-                        // Update suggestions dynamically
-                        if !input.is_empty() {
-                            let input_upper = input.to_uppercase();
-                            let mut matches: Vec<String> = SQL_KEYWORDS
-                                .iter()
-                                .filter(|kw| kw.starts_with(&input_upper))
-                                .map(|s| s.to_string())
-                                .collect();
-
-                            let saved = load_saved_queries();
-                            matches.extend(
-                                saved.iter()
-                                    .filter(|q| q.name.to_uppercase().starts_with(&input_upper))
-                                    .map(|q| format!("Saved: {}", q.name))
-                            );
-                            suggestions = matches;
-                        } else {
-                            suggestions.clear();
+                // The sidebar takes movement keys and Enter while it has focus
+                if focus == Focus::Sidebar && !matches!(action, Some(Action::ToggleFocus | Action::ToggleSidebar)) {
+                    match sidebar.handle_key(code) {
+                        SidebarKey::Ignored => {}
+                        SidebarKey::Handled => continue,
+                        SidebarKey::Action(_) if running.is_some() => {
+                            output = vec![format!(
+                                "A query is still running; press {} to cancel it.",
+                                keymap.key_for(Action::Cancel)
+                            )];
+                            continue;
+                        }
+                        SidebarKey::Action(SidebarAction::Preview(name)) => {
+                            let sql = format!("SELECT * FROM {} LIMIT 100", identifier(&name));
+                            running = Some(RunningQuery::start(conn, sql, QueryParams::default()));
+                            output = vec!["Running query…".into()];
+                            focus = Focus::Results;
+                            continue;
+                        }
+                        SidebarKey::Action(SidebarAction::Analyze(name)) => {
+                            output = vec![format!("Analyzing '{}'…", name)];
+                            running = Some(RunningQuery::analyze(conn, name));
+                            focus = Focus::Results;
+                            continue;
+                        }
+                        SidebarKey::Action(SidebarAction::ShowCreate(sql)) => {
+                            output = sql.lines().map(String::from).collect();
+                            output_scroll = 0;
+                            continue;
+                        }
+                        SidebarKey::Action(SidebarAction::InsertName(name)) => {
+                            editor.complete_word(0, &identifier(&name));
+                            focus = Focus::Editor;
+                            continue;
                         }
                     }
+                }
 
-                    InputMode::SaveName(query_text) => {
-                        match code {
-                            KeyCode::Char(c) => input.push(c),
-                            KeyCode::Backspace => { input.pop(); }
-                            KeyCode::Enter => {
-                                if !input.trim().is_empty() {
-                                    // Save with user provided name
-                                    save_new_query(&input, query_text)?;
-                                    output = vec![format!("Saved query as '{}'.", input)];
-                                } else {
-                                    // Save as "Unnamed Query"
-                                    save_new_query("Unnamed Query", query_text)?;
-                                    output = vec!["Saved query as 'Unnamed Query'.".into()];
+                match action {
+                    Some(Action::SavedQueries) => {
+                        match SavedScreen::open(stores.clone()) {
+                            Ok(Some(screen)) => input_mode = InputMode::SelectSaved(screen),
+                            Ok(None) => output = vec!["No saved queries.".into()],
+                            Err(e) => output = vec![format!("Could not load saved queries: {e:#}")],
+                        }
+                    }
+                    Some(Action::ToggleFocus) => {
+                        focus = match focus {
+                            Focus::Editor => Focus::Results,
+                            Focus::Results if show_sidebar => Focus::Sidebar,
+                            Focus::Results | Focus::Sidebar => Focus::Editor,
+                        };
+                    }
+                    Some(Action::ToggleSidebar) => {
+                        show_sidebar = !show_sidebar;
+                        focus = if show_sidebar { Focus::Sidebar } else { Focus::Editor };
+                    }
+                    Some(Action::SaveQuery) if !editor.is_blank() => {
+                        // Save current query
+                        let scope = stores.default_scope();
+                        input_mode = InputMode::SaveName { sql: editor.text(), scope };
+                        name_input.clear();
+                        output = save_prompt(&name_input, stores, scope);
+                    }
+                    Some(Action::Complete) => {
+                        // A single match is completed straight away; several open the popup
+                        (completing, suggestions) = suggest(&schema, &editor);
+                        match suggestions.len() {
+                            0 => {}
+                            1 => editor.complete_word(completing, &suggestions[0].text),
+                            _ => popup = Some(0),
+                        }
+                        focus = Focus::Editor;
+                    }
+                    Some(Action::Cancel) if running.is_some() => {
+                        if let Some(query) = &mut running {
+                            query.cancel();
+                        }
+                    }
+                    Some(Action::Execute) if running.is_some() => {
+                        output = vec![format!(
+                            "A query is still running; press {} to cancel it.",
+                            keymap.key_for(Action::Cancel)
+                        )];
+                    }
+                    Some(Action::Execute) if !editor.is_blank() => {
+                        // Run on the worker; the result is picked up at the top of the loop
+                        popup = None;
+                        let sql = editor.text();
+                        let params = match &bound {
+                            Some((template, params)) if *template == sql => params.clone(),
+                            _ => QueryParams::default(),
+                        };
+                        running = Some(RunningQuery::start(conn, sql, params));
+                        output = vec!["Running query…".into()];
+                        recall = None;
+                    }
+                    Some(Action::HistorySearch) => {
+                        input_mode = InputMode::Search { query: String::new(), found: None };
+                        output = search_view(history, "", None);
+                    }
+                    Some(Action::NewLine) => {
+                        editor.newline();
+                        focus = Focus::Editor;
+                    }
+                    Some(Action::Undo) => editor.undo(),
+                    Some(Action::Redo) => editor.redo(),
+                    None if code == KeyCode::Up && focus == Focus::Editor && editor.on_first_line() => {
+                        // Recall the previous statement that differs from the current text
+                        let current = editor.text();
+                        let before = recall.as_ref().map(|(i, _)| *i).unwrap_or(history.entries().len());
+                        if let Some(i) = history.entries()[..before].iter().rposition(|e| e.sql != current) {
+                            let draft = recall.take().map(|(_, draft)| draft).unwrap_or(current);
+                            editor.set_text(&history.entries()[i].sql);
+                            recall = Some((i, draft));
+                        }
+                    }
+                    None if code == KeyCode::Down && focus == Focus::Editor && editor.on_last_line() && recall.is_some() => {
+                        // Step forward through history, back to the unfinished text at the end
+                        let current = editor.text();
+                        if let Some((i, draft)) = recall.take() {
+                            match history.entries()[i + 1..].iter().position(|e| e.sql != current) {
+                                Some(offset) => {
+                                    editor.set_text(&history.entries()[i + 1 + offset].sql);
+                                    recall = Some((i + 1 + offset, draft));
                                 }
-                                input.clear();
-                                input_mode = InputMode::SQL;
-                            }
-                            KeyCode::Esc => {
-                                input.clear();
-                                output = vec!["Save cancelled.".into()];
-                                input_mode = InputMode::SQL;
+                                None => editor.set_text(&draft),
                             }
-                            _ => {}
                         }
                     }
+                    _ if focus == Focus::Results && output.is_empty() && grid.handle_key(code) => {}
+                    // Messages that do not fit, such as reports, scroll instead
+                    None if focus == Focus::Results && !output.is_empty()
+                        && matches!(code, KeyCode::Up | KeyCode::Down | KeyCode::PageUp | KeyCode::PageDown | KeyCode::Home | KeyCode::End) =>
+                    {
+                        output_scroll = match code {
+                            KeyCode::Up => output_scroll.saturating_sub(1),
+                            KeyCode::Down => output_scroll + 1,
+                            KeyCode::PageUp => output_scroll.saturating_sub(output_page),
+                            KeyCode::PageDown => output_scroll + output_page,
+                            KeyCode::Home => 0,
+                            _ => usize::MAX,
+                        };
+                    }
+                    _ => {
+                        // Anything else is editing, which brings the editor back into focus
+                        if editor.handle_key(code, modifiers) {
+                            focus = Focus::Editor;
+                        }
+                    }
+                }
 
-                    InputMode::SelectSaved(_saved_list) => {
-                        match code {
-                            KeyCode::Char(c) if c.is_ascii_digit() => {
-                                number_buffer.push(c);
-                                output = vec![format!("Select query number: {}", number_buffer)];
-                            }
-                            KeyCode::Enter => {
-                                if let Ok(index) = number_buffer.parse::<usize>() {
-                                    let saved_queries = load_saved_queries();
-                                    if index >= 1 && index <= saved_queries.len() {
-                                        input = saved_queries[index - 1].sql.clone();
-                                        output = vec![format!("Loaded query '{}'.", saved_queries[index - 1].name)];
-                                    } else {
-                                        output = vec!["Invalid selection.".into()];
-                                    }
-                                }
-                                number_buffer.clear();
-                                input_mode = InputMode::SQL;
+                // Keep an open popup in step with what is typed
+                if popup.is_some() {
+                    (completing, suggestions) = suggest(&schema, &editor);
+                    let still_editing = matches!(input_mode, InputMode::Sql) && focus == Focus::Editor;
+                    popup = if still_editing && !suggestions.is_empty() { Some(0) } else { None };
+                }
+            }
+
+            InputMode::SaveName { sql, scope } => {
+                match code {
+                    KeyCode::Char(c) => {
+                        name_input.push(c);
+                        output = save_prompt(&name_input, stores, *scope);
+                    }
+                    KeyCode::Backspace => {
+                        name_input.pop();
+                        output = save_prompt(&name_input, stores, *scope);
+                    }
+                    // Tab moves on to the next store
+                    KeyCode::Tab => {
+                        let scopes: Vec<Scope> = stores.stores().iter().map(|store| store.scope).collect();
+                        let next = scopes.iter().position(|s| s == scope).map_or(0, |i| (i + 1) % scopes.len());
+                        *scope = scopes[next];
+                        output = save_prompt(&name_input, stores, *scope);
+                    }
+                    KeyCode::Enter => {
+                        let name = if name_input.trim().is_empty() { "Unnamed Query" } else { name_input.trim() };
+                        match stores.exists(name, *scope) {
+                            Ok(true) => {
+                                output = vec![format!(
+                                    "A {} saved query named '{}' already exists. Overwrite it? (y/n)",
+                                    scope.name(),
+                                    name
+                                )];
+                                input_mode = InputMode::ConfirmOverwrite { name: name.to_string(), sql: std::mem::take(sql), scope: *scope };
                             }
-                            KeyCode::Esc => {
-                                number_buffer.clear();
-                                input_mode = InputMode::SQL;
-                                output = vec!["Cancelled loading saved query.".into()];
+                            Ok(false) => {
+                                output = match stores.add(SavedQuery::new(name, sql), Some(*scope), false) {
+                                    Ok(scope) => vec![format!("Saved query as '{}' ({}).", name, scope.name())],
+                                    Err(e) => vec![format!("Could not save query: {e:#}")],
+                                };
+                                input_mode = InputMode::Sql;
                             }
-                            KeyCode::Backspace => {
-                                number_buffer.pop();
-                                output = vec![format!("Select query number: {}", number_buffer)];
+                            Err(e) => {
+                                output = vec![format!("Could not save query: {e:#}")];
+                                input_mode = InputMode::Sql;
                             }
-                            _ => {}
                         }
                     }
+                    KeyCode::Esc => {
+                        output = vec!["Save cancelled.".into()];
+                        input_mode = InputMode::Sql;
+                    }
+                    _ => {}
+                }
+            }
+
+            InputMode::ConfirmOverwrite { name, sql, scope } => {
+                output = if matches!(code, KeyCode::Char('y' | 'Y')) {
+                    match stores.add(SavedQuery::new(name, sql), Some(*scope), true) {
+                        Ok(_) => vec![format!("Replaced saved query '{}'.", name)],
+                        Err(e) => vec![format!("Could not save query: {e:#}")],
+                    }
+                } else {
+                    vec!["Save cancelled.".into()]
+                };
+                input_mode = InputMode::Sql;
+            }
+
+            InputMode::SelectSaved(screen) => {
+                match screen.handle_key(code, &editor.text()) {
+                    SavedKey::Handled => {}
+                    SavedKey::Close(message) => {
+                        output = vec![message];
+                        input_mode = InputMode::Sql;
+                    }
+                    SavedKey::Load(query) if !query.params.is_empty() => {
+                        input_mode = InputMode::FillParams(ParamForm::new(query));
+                    }
+                    SavedKey::Load(query) => {
+                        editor.set_text(&query.sql);
+                        bound = None;
+                        output = vec![format!("Loaded query '{}'.", query.name)];
+                        input_mode = InputMode::Sql;
+                    }
+                }
+            }
+
+            InputMode::FillParams(form) => {
+                match form.handle_key(code) {
+                    FormKey::Handled => {}
+                    FormKey::Cancel => {
+                        output = vec![format!("Did not load '{}'.", form.name())];
+                        input_mode = InputMode::Sql;
+                    }
+                    FormKey::Submit(sql, params) => {
+                        editor.set_text(&sql);
+                        recall = None;
+                        output = if running.is_some() {
+                            vec![format!(
+                                "Loaded '{}'; a query is still running, so press {} to run it once that is done.",
+                                form.name(),
+                                keymap.key_for(Action::Execute)
+                            )]
+                        } else {
+                            running = Some(RunningQuery::start(conn, sql.clone(), params.clone()));
+                            vec!["Running query…".into()]
+                        };
+                        bound = Some((sql, params));
+                        input_mode = InputMode::Sql;
+                    }
+                }
+            }
+
+            InputMode::Search { query, found } => {
+                match code {
+                    // Pressing the search key again moves to the next older match
+                    _ if action == Some(Action::HistorySearch) => {
+                        let before = found.unwrap_or(history.entries().len());
+                        if let Some(older) = history.search(query, before) {
+                            *found = Some(older);
+                        }
+                    }
+                    KeyCode::Char(c) if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => {
+                        query.push(c);
+                        *found = history.search(query, history.entries().len());
+                    }
+                    KeyCode::Backspace => {
+                        query.pop();
+                        *found = if query.is_empty() { None } else { history.search(query, history.entries().len()) };
+                    }
+                    KeyCode::Enter => {
+                        output = match found {
+                            Some(i) => {
+                                editor.set_text(&history.entries()[*i].sql);
+                                vec!["Loaded statement from history.".into()]
+                            }
+                            None => vec!["No matching history entry.".into()],
+                        };
+                        input_mode = InputMode::Sql;
+                        continue;
+                    }
+                    KeyCode::Esc => {
+                        output = vec!["History search cancelled.".into()];
+                        input_mode = InputMode::Sql;
+                        continue;
+                    }
+                    _ => {}
                 }
+                output = search_view(history, query, *found);
             }
         }
     }
 
-    // Cleanup
-    disable_raw_mode()?;
+    Ok(())
+}
 
-    let mut stdout = io::stdout();
-    execute!(stdout, LeaveAlternateScreen, cursor::Show)?;
+/// Completions for the word at the editor's cursor
+fn suggest(schema: &SchemaCache, editor: &Editor) -> (usize, Vec<Suggestion>) {
+    let (before, after) = editor.split_at_cursor();
+    schema.complete(&before, &after)
+}
 
-    Ok(())
-}
\ No newline at end of file
+fn save_prompt(name: &str, stores: &SavedStores, scope: Scope) -> Vec<String> {
+    let mut lines = vec!["Enter a name for this query and press Enter:".into(), format!("> {}", name), String::new()];
+    if let Ok(store) = stores.store(scope) {
+        lines.push(format!("Saving to the {} queries in {}", scope.name(), store.path.display()));
+    }
+    if stores.stores().len() > 1 {
+        lines.push("Tab saves somewhere else.".into());
+    }
+    lines
+}
+
+/// Prompt and current match for the reverse history search
+fn search_view(history: &History, query: &str, found: Option<usize>) -> Vec<String> {
+    let mut lines = vec![format!("(reverse-i-search)`{}':", query)];
+    match found {
+        Some(i) => lines.extend(history.entries()[i].sql.lines().map(String::from)),
+        None if !query.is_empty() => lines.push("(no match)".into()),
+        None => {}
+    }
+    lines.push(String::new());
+    lines.push("Type to search, Enter to load, Esc to cancel.".into());
+    lines
+}
@@ -0,0 +1,418 @@
+use anyhow::Result;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+};
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+use crate::sql::quote_ident;
+
+const SQL_KEYWORDS: &[&str] = &[
+    "SELECT", "FROM", "WHERE", "INSERT", "INTO", "UPDATE", "DELETE", "CREATE", "DROP", "ALTER",
+    "TABLE", "INDEX", "VIEW", "TRIGGER", "JOIN", "INNER", "LEFT", "RIGHT", "FULL", "OUTER", "CROSS",
+    "ON", "USING", "AS", "GROUP BY", "ORDER BY", "HAVING", "LIMIT", "OFFSET", "VALUES", "SET", "AND",
+    "OR", "NOT", "NULL", "IS", "IN", "LIKE", "GLOB", "BETWEEN", "EXISTS", "DISTINCT", "CASE", "WHEN",
+    "THEN", "ELSE", "END", "UNION", "ALL", "EXCEPT", "INTERSECT", "WITH", "RECURSIVE", "ASC", "DESC",
+    "PRIMARY KEY", "FOREIGN KEY", "REFERENCES", "DEFAULT", "UNIQUE", "CHECK", "BEGIN", "COMMIT",
+    "ROLLBACK", "PRAGMA", "EXPLAIN", "QUERY PLAN", "RETURNING", "REPLACE", "VACUUM", "ANALYZE",
+];
+
+/// Keywords after which a table or view name is expected
+const TABLE_CONTEXT: &[&str] = &["FROM", "JOIN", "INTO", "UPDATE", "TABLE"];
+
+/// Keywords that end the list of tables in a FROM clause
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "WHERE", "GROUP", "ORDER", "HAVING", "LIMIT", "ON", "USING", "SET", "VALUES", "UNION", "EXCEPT",
+    "INTERSECT", "WINDOW", "RETURNING",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Column,
+    Table,
+    View,
+    Function,
+    Keyword,
+}
+
+impl Kind {
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Column => "column",
+            Kind::Table => "table",
+            Kind::View => "view",
+            Kind::Function => "function",
+            Kind::Keyword => "keyword",
+        }
+    }
+}
+
+/// A completion candidate and the text it inserts
+#[derive(Clone, Debug)]
+pub struct Suggestion {
+    pub text: String,
+    pub kind: Kind,
+    /// Character positions in `text` that matched what was typed
+    pub matched: Vec<usize>,
+}
+
+struct TableNames {
+    name: String,
+    kind: Kind,
+    columns: Vec<String>,
+}
+
+/// Table, view, column and function names, read once and refreshed after schema changes
+pub struct SchemaCache {
+    tables: Vec<TableNames>,
+    functions: Vec<String>,
+}
+
+impl SchemaCache {
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let mut stmt = conn.prepare(
+            "SELECT name, type FROM sqlite_schema
+             WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )?;
+        let names = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut column_stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+        let mut tables = Vec::new();
+        for (name, kind) in names {
+            let columns = column_stmt
+                .query_map([&name], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            let kind = if kind == "view" { Kind::View } else { Kind::Table };
+            tables.push(TableNames { name, kind, columns });
+        }
+
+        let functions: Vec<String> = match conn.prepare("SELECT DISTINCT name FROM pragma_function_list ORDER BY name") {
+            Ok(mut stmt) => stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?,
+            // SQLite builds without function introspection just get no function names
+            Err(_) => Vec::new(),
+        };
+        // Operators such as `->` are listed too; only names can be typed as identifiers
+        let functions = functions
+            .into_iter()
+            .filter(|name| name.chars().all(is_word_char) && !name.starts_with("sqlite_"))
+            .collect();
+
+        Ok(SchemaCache { tables, functions })
+    }
+
+    /// Suggestions for the token ending at the cursor, along with the token's length in characters.
+    ///
+    /// `before` and `after` are the editor text on either side of the cursor.
+    pub fn complete(&self, before: &str, after: &str) -> (usize, Vec<Suggestion>) {
+        let (qualifier, prefix) = token_at_end(before);
+        let prefix_len = prefix.chars().count();
+
+        // Only the statement the cursor is in matters
+        let start = before.rfind(';').map(|i| i + 1).unwrap_or(0);
+        let end = after.find(';').unwrap_or(after.len());
+        let statement = format!("{}{}", &before[start..], &after[..end]);
+        let preceding = previous_keyword(&before[start..before.len() - prefix.len()]);
+
+        let mut candidates: Vec<Suggestion> = Vec::new();
+        match &qualifier {
+            // `alias.` or `table.`: that table's columns
+            Some(qualifier) => {
+                let aliases = table_aliases(&statement);
+                let target = aliases.get(&qualifier.to_lowercase()).cloned().unwrap_or(qualifier.clone());
+                if let Some(table) = self.table(&target) {
+                    candidates.extend(table.columns.iter().map(|c| suggestion(c, Kind::Column)));
+                } else if matches!(qualifier.to_lowercase().as_str(), "main" | "temp") {
+                    candidates.extend(self.tables.iter().map(|t| suggestion(&t.name, t.kind)));
+                }
+            }
+            None if preceding.as_deref().is_some_and(|kw| TABLE_CONTEXT.contains(&kw)) => {
+                candidates.extend(self.tables.iter().map(|t| suggestion(&t.name, t.kind)));
+            }
+            None => {
+                if prefix.is_empty() {
+                    return (0, Vec::new());
+                }
+                // Columns of the tables this statement uses come first
+                let aliases = table_aliases(&statement);
+                let mut used: Vec<&String> = aliases.values().collect();
+                used.sort();
+                used.dedup();
+                for table in used.into_iter().filter_map(|name| self.table(name)) {
+                    candidates.extend(table.columns.iter().map(|c| suggestion(c, Kind::Column)));
+                }
+                candidates.extend(self.tables.iter().map(|t| suggestion(&t.name, t.kind)));
+                candidates.extend(self.functions.iter().map(|f| suggestion(f, Kind::Function)));
+                candidates.extend(SQL_KEYWORDS.iter().map(|kw| Suggestion {
+                    text: match_case(kw, &prefix),
+                    kind: Kind::Keyword,
+                    matched: Vec::new(),
+                }));
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let mut scored: Vec<(i64, Suggestion)> = candidates
+            .into_iter()
+            .filter(|s| seen.insert(s.text.to_lowercase()))
+            // Nothing to complete when the token is already typed in full
+            .filter(|s| !s.text.eq_ignore_ascii_case(&prefix))
+            .filter_map(|mut s| {
+                let (score, matched) = fuzzy_match(&prefix, &s.text)?;
+                s.matched = matched;
+                Some((score, s))
+            })
+            .collect();
+        // Stable, so equal scores keep the context order above (columns before tables, and so on)
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        (prefix_len, scored.into_iter().map(|(_, s)| s).collect())
+    }
+
+    fn table(&self, name: &str) -> Option<&TableNames> {
+        let name = unquoted(name.rsplit('.').next().unwrap_or(name));
+        self.tables.iter().find(|t| t.name.eq_ignore_ascii_case(&name))
+    }
+}
+
+/// Suggestions shown in the popup at once
+const POPUP_ROWS: usize = 8;
+
+/// Draws the suggestion list under the word being completed, or above it when there is no room below
+pub fn render_popup(f: &mut Frame, suggestions: &[Suggestion], selected: usize, anchor: (u16, u16)) {
+    let screen = f.size();
+    let text_width = suggestions.iter().map(|s| s.text.chars().count() + s.kind.label().len() + 2).max().unwrap_or(0);
+    let width = (text_width as u16 + 2).min(screen.width);
+    let height = (suggestions.len().min(POPUP_ROWS) as u16 + 2).min(screen.height);
+
+    let x = anchor.0.min(screen.width.saturating_sub(width));
+    let below = anchor.1 + 1;
+    let y = if below + height <= screen.height { below } else { anchor.1.saturating_sub(height) };
+    let area = Rect::new(x, y, width, height);
+
+    let matched_style = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let items: Vec<ListItem> = suggestions
+        .iter()
+        .map(|s| {
+            let mut spans: Vec<Span> = s
+                .text
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    if s.matched.contains(&i) {
+                        Span::styled(c.to_string(), matched_style)
+                    } else {
+                        Span::raw(c.to_string())
+                    }
+                })
+                .collect();
+            let padding = text_width - s.text.chars().count() - s.kind.label().len();
+            spans.push(Span::raw(" ".repeat(padding)));
+            spans.push(Span::styled(s.kind.label(), Style::default().fg(Color::DarkGray)));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default().with_selected(Some(selected));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+/// Scores `candidate` as a case-insensitive subsequence match of `pattern`.
+///
+/// Prefix matches rank first, then matches with consecutive characters or at
+/// word starts (`first_name` for `fn`). Returns the matched character positions.
+fn fuzzy_match(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let offset = if candidate.starts_with(['"', '`', '[']) { 1 } else { 0 };
+
+    // A prefix match (after any opening quote) is the best match there is
+    if lower[offset..].starts_with(&pattern) {
+        let score = 1000 - (chars.len() - pattern.len()) as i64;
+        return Some((score, (offset..offset + pattern.len()).collect()));
+    }
+
+    let mut matched = Vec::with_capacity(pattern.len());
+    let mut score = 0;
+    let mut next = 0;
+    for p in &pattern {
+        let at_boundary = |i: usize| i == offset || !lower[i - 1].is_alphanumeric() || (chars[i].is_uppercase() && chars[i - 1].is_lowercase());
+        // Prefer continuing the current run, then a word start, then any later occurrence
+        let found = Some(next)
+            .filter(|&i| !matched.is_empty() && i < chars.len() && lower[i] == *p)
+            .or_else(|| (next..chars.len()).find(|&i| lower[i] == *p && at_boundary(i)))
+            .or_else(|| (next..chars.len()).find(|&i| lower[i] == *p))?;
+        if at_boundary(found) {
+            score += 8;
+        }
+        if matched.last().is_some_and(|&last| found == last + 1) {
+            score += 10;
+        }
+        matched.push(found);
+        next = found + 1;
+    }
+    let span = matched.last().unwrap_or(&0) - matched.first().unwrap_or(&0);
+    score -= (span + 1 - matched.len()) as i64 + chars.len() as i64 / 4;
+    Some((score, matched))
+}
+
+/// Splits the end of `text` into an optional `qualifier.` and the word being typed
+fn token_at_end(text: &str) -> (Option<String>, String) {
+    let prefix: String = text.chars().rev().take_while(|c| is_word_char(*c)).collect::<Vec<_>>().into_iter().rev().collect();
+    let rest = &text[..text.len() - prefix.len()];
+    let qualifier = rest.strip_suffix('.').and_then(|rest| {
+        let name: String = if rest.ends_with(['"', '`', ']']) {
+            // Quoted qualifier, e.g. "my table".
+            let open = match rest.chars().last() {
+                Some(']') => '[',
+                Some(c) => c,
+                None => return None,
+            };
+            let inner = &rest[..rest.len() - 1];
+            let start = inner.rfind(open)?;
+            inner[start + 1..].to_string()
+        } else {
+            rest.chars().rev().take_while(|c| is_word_char(*c)).collect::<Vec<_>>().into_iter().rev().collect()
+        };
+        (!name.is_empty()).then_some(name)
+    });
+    (qualifier, prefix)
+}
+
+/// The last keyword-looking word in `text`, uppercased
+fn previous_keyword(text: &str) -> Option<String> {
+    text.split(|c: char| !is_word_char(c) && c != '.')
+        .rev()
+        .find(|w| !w.is_empty())
+        .map(|w| w.to_uppercase())
+}
+
+/// Maps aliases and table names (lowercased) to the tables named after FROM, JOIN, UPDATE and INTO
+fn table_aliases(statement: &str) -> HashMap<String, String> {
+    let words = words(statement);
+    let mut aliases = HashMap::new();
+    let mut in_from = false;
+    let mut i = 0;
+    while i < words.len() {
+        let upper = words[i].to_uppercase();
+        let starts_table = matches!(upper.as_str(), "JOIN" | "UPDATE" | "INTO")
+            || upper == "FROM"
+            || (in_from && words[i] == ",");
+        if upper == "FROM" {
+            in_from = true;
+        } else if CLAUSE_KEYWORDS.contains(&upper.as_str()) || upper == "SELECT" {
+            in_from = false;
+        }
+        if starts_table && let Some(table) = words.get(i + 1).filter(|w| is_name(w)) {
+            let table = unquoted(table);
+            aliases.insert(table.rsplit('.').next().unwrap_or(&table).to_lowercase(), table.clone());
+            let mut next = i + 2;
+            if words.get(next).is_some_and(|w| w.eq_ignore_ascii_case("AS")) {
+                next += 1;
+            }
+            if let Some(alias) = words.get(next).filter(|w| is_name(w) && !is_keyword(w)) {
+                aliases.insert(unquoted(alias).to_lowercase(), table);
+            }
+            i += 1;
+        }
+        i += 1;
+    }
+    aliases
+}
+
+/// Splits SQL into names (with dots and quotes kept together), commas and other punctuation
+fn words(sql: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for c in sql.chars() {
+        if let Some(close) = quote {
+            current.push(c);
+            if c == close {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '`' => {
+                current.push(c);
+                quote = Some(c);
+            }
+            '[' => {
+                current.push(c);
+                quote = Some(']');
+            }
+            c if is_word_char(c) || c == '.' => current.push(c),
+            c => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+                if !c.is_whitespace() {
+                    words.push(c.to_string());
+                }
+            }
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn is_name(word: &str) -> bool {
+    word.starts_with(['"', '`', '[']) || word.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+}
+
+fn is_keyword(word: &str) -> bool {
+    let upper = word.to_uppercase();
+    SQL_KEYWORDS.iter().any(|kw| kw.split(' ').next() == Some(upper.as_str()))
+        || CLAUSE_KEYWORDS.contains(&upper.as_str())
+        || matches!(upper.as_str(), "NATURAL" | "WINDOW")
+}
+
+/// Removes identifier quotes, e.g. `"my table"` becomes `my table`
+fn unquoted(name: &str) -> String {
+    let trimmed = name.trim();
+    match trimmed.chars().next() {
+        Some('"') => trimmed.trim_matches('"').replace("\"\"", "\""),
+        Some('`') => trimmed.trim_matches('`').replace("``", "`"),
+        Some('[') => trimmed.trim_start_matches('[').trim_end_matches(']').to_string(),
+        _ => trimmed.to_string(),
+    }
+}
+
+/// Quotes names that cannot be typed bare
+fn suggestion(name: &str, kind: Kind) -> Suggestion {
+    Suggestion { text: identifier(name), kind, matched: Vec::new() }
+}
+
+/// A name as it should be typed into SQL, quoted only when it cannot be written bare
+pub fn identifier(name: &str) -> String {
+    let bare = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && name.chars().all(is_word_char)
+        && !is_keyword(name);
+    if bare { name.to_string() } else { quote_ident(name) }
+}
+
+/// Keywords follow the case the user is typing in
+fn match_case(keyword: &str, typed: &str) -> String {
+    if !typed.is_empty() && typed.chars().all(|c| !c.is_uppercase()) {
+        keyword.to_lowercase()
+    } else {
+        keyword.to_string()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
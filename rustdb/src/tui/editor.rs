@@ -0,0 +1,367 @@
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    text::{Line, Text},
+    widgets::{Block, Paragraph},
+};
+
+/// Undo steps kept before the oldest is dropped
+const MAX_UNDO: usize = 200;
+
+/// Text and cursor, as stored on the undo and redo stacks
+#[derive(Clone)]
+struct Snapshot {
+    lines: Vec<String>,
+    row: usize,
+    col: usize,
+}
+
+/// What the last edit was, so runs of typing undo as one step
+#[derive(PartialEq)]
+enum EditKind {
+    Typing,
+    Other,
+}
+
+/// Multi-line SQL editor with a cursor and undo/redo
+pub struct Editor {
+    lines: Vec<String>,
+    /// Cursor line and character (not byte) column
+    row: usize,
+    col: usize,
+    row_offset: usize,
+    col_offset: usize,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    last_edit: Option<EditKind>,
+    /// Where the cursor was drawn, for placing popups next to it
+    screen_cursor: (u16, u16),
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Editor {
+            lines: vec![String::new()],
+            row: 0,
+            col: 0,
+            row_offset: 0,
+            col_offset: 0,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            last_edit: None,
+            screen_cursor: (0, 0),
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    pub fn is_blank(&self) -> bool {
+        self.lines.iter().all(|line| line.trim().is_empty())
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Text before and after the cursor
+    pub fn split_at_cursor(&self) -> (String, String) {
+        let at = self.byte_index(self.row, self.col);
+        let mut before = self.lines[..self.row].join("\n");
+        let mut after = self.lines[self.row][at..].to_string();
+        if self.row > 0 {
+            before.push('\n');
+        }
+        before.push_str(&self.lines[self.row][..at]);
+        for line in &self.lines[self.row + 1..] {
+            after.push('\n');
+            after.push_str(line);
+        }
+        (before, after)
+    }
+
+    /// Replaces the `replace` characters before the cursor with `word`, as one undo step
+    pub fn complete_word(&mut self, replace: usize, word: &str) {
+        self.checkpoint(EditKind::Other);
+        let start = self.byte_index(self.row, self.col.saturating_sub(replace));
+        let end = self.byte_index(self.row, self.col);
+        self.lines[self.row].replace_range(start..end, word);
+        self.col = self.col.saturating_sub(replace) + word.chars().count();
+    }
+
+    pub fn on_first_line(&self) -> bool {
+        self.row == 0
+    }
+
+    pub fn on_last_line(&self) -> bool {
+        self.row + 1 == self.lines.len()
+    }
+
+    /// Replaces the whole text, leaving the cursor at the end; can be undone
+    pub fn set_text(&mut self, text: &str) {
+        self.checkpoint(EditKind::Other);
+        self.lines = text.split('\n').map(|line| line.trim_end_matches('\r').to_string()).collect();
+        self.row = self.lines.len() - 1;
+        self.col = self.line_len(self.row);
+    }
+
+    /// Handles typing, deletion and cursor movement; returns false for keys the editor does not use
+    pub fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        let alt = modifiers.contains(KeyModifiers::ALT);
+        match code {
+            KeyCode::Char(c) if !ctrl && !alt => self.insert_char(c),
+            KeyCode::Backspace => self.backspace(),
+            KeyCode::Delete => self.delete(),
+            KeyCode::Left if ctrl || alt => self.word_left(),
+            KeyCode::Right if ctrl || alt => self.word_right(),
+            KeyCode::Left => self.left(),
+            KeyCode::Right => self.right(),
+            KeyCode::Up => self.up(),
+            KeyCode::Down => self.down(),
+            KeyCode::Home if ctrl => (self.row, self.col) = (0, 0),
+            KeyCode::End if ctrl => {
+                self.row = self.lines.len() - 1;
+                self.col = self.line_len(self.row);
+            }
+            KeyCode::Home => self.col = 0,
+            KeyCode::End => self.col = self.line_len(self.row),
+            _ => return false,
+        }
+        if !matches!(code, KeyCode::Char(_) | KeyCode::Backspace | KeyCode::Delete) {
+            // Moving the cursor ends a run of typing
+            self.last_edit = None;
+        }
+        true
+    }
+
+    /// Inserts pasted text at the cursor as a single undo step
+    pub fn paste(&mut self, text: &str) {
+        self.checkpoint(EditKind::Other);
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let tail = self.split_off_tail();
+        let mut pasted = text.split('\n');
+        if let Some(first) = pasted.next() {
+            self.lines[self.row].push_str(first);
+        }
+        for line in pasted {
+            self.row += 1;
+            self.lines.insert(self.row, line.to_string());
+        }
+        self.col = self.line_len(self.row);
+        self.lines[self.row].push_str(&tail);
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, block: Block, show_cursor: bool) {
+        let inner = block.inner(area);
+        let height = (inner.height as usize).max(1);
+        let width = (inner.width as usize).max(1);
+
+        // Keep the cursor inside the visible window
+        if self.row < self.row_offset {
+            self.row_offset = self.row;
+        } else if self.row >= self.row_offset + height {
+            self.row_offset = self.row + 1 - height;
+        }
+        if self.col < self.col_offset {
+            self.col_offset = self.col;
+        } else if self.col >= self.col_offset + width {
+            self.col_offset = self.col + 1 - width;
+        }
+
+        let text = Text::from(
+            self.lines
+                .iter()
+                .skip(self.row_offset)
+                .take(height)
+                .map(|line| Line::from(line.chars().skip(self.col_offset).collect::<String>()))
+                .collect::<Vec<Line>>(),
+        );
+        f.render_widget(Paragraph::new(text).block(block), area);
+
+        self.screen_cursor = (
+            inner.x + (self.col - self.col_offset) as u16,
+            inner.y + (self.row - self.row_offset) as u16,
+        );
+        if show_cursor {
+            f.set_cursor(self.screen_cursor.0, self.screen_cursor.1);
+        }
+    }
+
+    pub fn screen_cursor(&self) -> (u16, u16) {
+        self.screen_cursor
+    }
+
+    fn insert_char(&mut self, c: char) {
+        // A run of typing is one undo step, broken at word boundaries
+        let kind = if c.is_whitespace() { EditKind::Other } else { EditKind::Typing };
+        self.checkpoint(kind);
+        let at = self.byte_index(self.row, self.col);
+        self.lines[self.row].insert(at, c);
+        self.col += 1;
+    }
+
+    pub fn newline(&mut self) {
+        self.checkpoint(EditKind::Other);
+        let tail = self.split_off_tail();
+        self.row += 1;
+        self.lines.insert(self.row, tail);
+        self.col = 0;
+    }
+
+    fn backspace(&mut self) {
+        if self.col > 0 {
+            self.checkpoint(EditKind::Other);
+            self.col -= 1;
+            let at = self.byte_index(self.row, self.col);
+            self.lines[self.row].remove(at);
+        } else if self.row > 0 {
+            self.checkpoint(EditKind::Other);
+            let line = self.lines.remove(self.row);
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.col < self.line_len(self.row) {
+            self.checkpoint(EditKind::Other);
+            let at = self.byte_index(self.row, self.col);
+            self.lines[self.row].remove(at);
+        } else if self.row + 1 < self.lines.len() {
+            self.checkpoint(EditKind::Other);
+            let line = self.lines.remove(self.row + 1);
+            self.lines[self.row].push_str(&line);
+        }
+    }
+
+    fn left(&mut self) {
+        if self.col > 0 {
+            self.col -= 1;
+        } else if self.row > 0 {
+            self.row -= 1;
+            self.col = self.line_len(self.row);
+        }
+    }
+
+    fn right(&mut self) {
+        if self.col < self.line_len(self.row) {
+            self.col += 1;
+        } else if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = 0;
+        }
+    }
+
+    fn up(&mut self) {
+        if self.row > 0 {
+            self.row -= 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    fn down(&mut self) {
+        if self.row + 1 < self.lines.len() {
+            self.row += 1;
+            self.col = self.col.min(self.line_len(self.row));
+        }
+    }
+
+    /// Moves to the start of the previous word, crossing line breaks
+    fn word_left(&mut self) {
+        if self.col == 0 {
+            self.left();
+            return;
+        }
+        let chars: Vec<char> = self.lines[self.row].chars().collect();
+        let mut col = self.col;
+        while col > 0 && !is_word_char(chars[col - 1]) {
+            col -= 1;
+        }
+        while col > 0 && is_word_char(chars[col - 1]) {
+            col -= 1;
+        }
+        self.col = col;
+    }
+
+    /// Moves past the end of the next word, crossing line breaks
+    fn word_right(&mut self) {
+        let chars: Vec<char> = self.lines[self.row].chars().collect();
+        if self.col == chars.len() {
+            self.right();
+            return;
+        }
+        let mut col = self.col;
+        while col < chars.len() && !is_word_char(chars[col]) {
+            col += 1;
+        }
+        while col < chars.len() && is_word_char(chars[col]) {
+            col += 1;
+        }
+        self.col = col;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            let current = self.snapshot();
+            self.redo.push(current);
+            self.restore(snapshot);
+        }
+        self.last_edit = None;
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(snapshot) = self.redo.pop() {
+            let current = self.snapshot();
+            self.undo.push(current);
+            self.restore(snapshot);
+        }
+        self.last_edit = None;
+    }
+
+    /// Records the state before an edit, unless it continues the current run of typing
+    fn checkpoint(&mut self, kind: EditKind) {
+        let continues = kind == EditKind::Typing && self.last_edit == Some(EditKind::Typing);
+        if !continues {
+            let snapshot = self.snapshot();
+            self.undo.push(snapshot);
+            if self.undo.len() > MAX_UNDO {
+                self.undo.remove(0);
+            }
+        }
+        self.redo.clear();
+        self.last_edit = Some(kind);
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot { lines: self.lines.clone(), row: self.row, col: self.col }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.lines = snapshot.lines;
+        self.row = snapshot.row;
+        self.col = snapshot.col;
+    }
+
+    /// Cuts the current line at the cursor, returning the part after it
+    fn split_off_tail(&mut self) -> String {
+        let at = self.byte_index(self.row, self.col);
+        self.lines[self.row].split_off(at)
+    }
+
+    fn line_len(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+
+    fn byte_index(&self, row: usize, col: usize) -> usize {
+        let line = &self.lines[row];
+        line.char_indices().nth(col).map(|(i, _)| i).unwrap_or(line.len())
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
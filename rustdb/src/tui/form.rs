@@ -0,0 +1,110 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    Frame,
+    layout::{Margin, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Paragraph},
+};
+
+use crate::params::{ParamType, QueryParams};
+use crate::saved_queries::SavedQuery;
+
+/// How the parameter form dealt with a key
+pub enum FormKey {
+    Handled,
+    Cancel,
+    /// Run this SQL with these values bound to its placeholders
+    Submit(String, QueryParams),
+}
+
+/// Values for a saved query's parameters, asked for before it runs
+pub struct ParamForm {
+    query: SavedQuery,
+    values: Vec<String>,
+    selected: usize,
+    /// Why the values were not accepted
+    error: Option<String>,
+}
+
+impl ParamForm {
+    /// A form with each field filled in with the parameter's default
+    pub fn new(query: SavedQuery) -> Self {
+        let values = query.params.iter().map(|p| p.default.clone().unwrap_or_default()).collect();
+        ParamForm { query, values, selected: 0, error: None }
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> FormKey {
+        let last = self.values.len() - 1;
+        match code {
+            KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.checked_sub(1).unwrap_or(last),
+            KeyCode::Down | KeyCode::Tab => self.selected = if self.selected == last { 0 } else { self.selected + 1 },
+            KeyCode::Char(c) => self.values[self.selected].push(c),
+            KeyCode::Backspace => {
+                self.values[self.selected].pop();
+            }
+            KeyCode::Enter => return self.submit(),
+            KeyCode::Esc => return FormKey::Cancel,
+            _ => {}
+        }
+        FormKey::Handled
+    }
+
+    /// Pasted text goes into the selected field
+    pub fn paste(&mut self, text: &str) {
+        self.values[self.selected].push_str(&text.replace(['\r', '\n'], " "));
+    }
+
+    pub fn name(&self) -> &str {
+        &self.query.name
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, block: Block) {
+        let inner = block.inner(area).inner(&Margin::new(1, 0));
+        f.render_widget(block, area);
+
+        let muted = Style::default().fg(Color::DarkGray);
+        let width = self.query.params.iter().map(|p| p.name.len()).max().unwrap_or(0);
+        let mut lines = vec![Line::from(format!("Values for '{}':", self.query.name)), Line::from("")];
+        for (i, (param, value)) in self.query.params.iter().zip(&self.values).enumerate() {
+            let mut spans = vec![
+                Span::raw(format!("{:>width$} ", param.name)),
+                Span::styled(format!("{:<8} ", param.kind.name()), muted),
+                Span::raw(value.clone()),
+            ];
+            if i == self.selected {
+                spans[0].style = Style::default().fg(Color::Cyan);
+                spans.push(Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)));
+            }
+            lines.push(Line::from(spans));
+        }
+        lines.push(Line::from(""));
+        if let Some(error) = &self.error {
+            lines.push(Line::styled(error.clone(), Style::default().fg(Color::Red)));
+        }
+        lines.push(Line::styled("Tab/↑↓ move  Enter run  Esc cancel", muted));
+        f.render_widget(Paragraph::new(Text::from(lines)), inner);
+    }
+
+    /// Checks every value against its type, moving to the first one that is wrong
+    fn submit(&mut self) -> FormKey {
+        let mut named = Vec::new();
+        for (i, (param, value)) in self.query.params.iter().zip(&self.values).enumerate() {
+            // An empty field is an empty string for text, and missing for anything else
+            let parsed = if value.trim().is_empty() && param.kind != ParamType::Text {
+                Err(anyhow::anyhow!("Enter a value"))
+            } else {
+                param.kind.parse(value)
+            };
+            match parsed {
+                Ok(value) => named.push((param.name.clone(), value)),
+                Err(e) => {
+                    self.selected = i;
+                    self.error = Some(format!("{}: {}", param.name, e));
+                    return FormKey::Handled;
+                }
+            }
+        }
+        FormKey::Submit(self.query.sql.clone(), QueryParams { positional: Vec::new(), named })
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::{Result, anyhow, bail};
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::config::KeyList;
+
+/// TUI actions that can be bound to keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Quit,
+    Execute,
+    NewLine,
+    SaveQuery,
+    SavedQueries,
+    ToggleFocus,
+    ToggleSidebar,
+    Complete,
+    Undo,
+    Redo,
+    HistorySearch,
+    Cancel,
+}
+
+impl Action {
+    const ALL: [Action; 12] = [
+        Action::Quit,
+        Action::Execute,
+        Action::NewLine,
+        Action::SaveQuery,
+        Action::SavedQueries,
+        Action::ToggleFocus,
+        Action::ToggleSidebar,
+        Action::Complete,
+        Action::Undo,
+        Action::Redo,
+        Action::HistorySearch,
+        Action::Cancel,
+    ];
+
+    /// Name used for the action in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Execute => "execute",
+            Action::NewLine => "newline",
+            Action::SaveQuery => "save_query",
+            Action::SavedQueries => "saved_queries",
+            Action::ToggleFocus => "toggle_focus",
+            Action::ToggleSidebar => "toggle_sidebar",
+            Action::Complete => "complete",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::HistorySearch => "history_search",
+            Action::Cancel => "cancel",
+        }
+    }
+
+    /// Whether the action changes the editor text, so it may take a key that would otherwise type a character
+    fn edits_text(self) -> bool {
+        matches!(self, Action::NewLine | Action::Complete | Action::Undo | Action::Redo)
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["ctrl+q"],
+            // Most terminals send Ctrl+J for Ctrl+Enter unless they report modifiers on Enter
+            Action::Execute => &["enter", "ctrl+enter", "ctrl+j"],
+            Action::NewLine => &["shift+enter", "alt+enter"],
+            Action::SaveQuery => &["ctrl+s"],
+            Action::SavedQueries => &["f2"],
+            Action::ToggleFocus => &["f6"],
+            Action::ToggleSidebar => &["f3"],
+            Action::Complete => &["tab"],
+            Action::Undo => &["ctrl+z"],
+            Action::Redo => &["ctrl+y", "ctrl+shift+z"],
+            Action::HistorySearch => &["ctrl+r"],
+            Action::Cancel => &["esc", "ctrl+c"],
+        }
+    }
+}
+
+/// A key plus modifiers, normalised so config entries and key events compare equal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let relevant = KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+        let mut modifiers = modifiers & relevant;
+        let code = match code {
+            // Letters are stored lowercase with Shift; Shift is already part of other characters
+            KeyCode::Char(c) if c.is_alphabetic() => {
+                if c.is_uppercase() {
+                    modifiers |= KeyModifiers::SHIFT;
+                }
+                KeyCode::Char(c.to_lowercase().next().unwrap_or(c))
+            }
+            KeyCode::Char(c) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::Char(c)
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers -= KeyModifiers::SHIFT;
+                KeyCode::BackTab
+            }
+            other => other,
+        };
+        KeyBinding { code, modifiers }
+    }
+
+    /// Whether pressing the key types a character into the editor
+    fn types_text(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && (self.modifiers - KeyModifiers::SHIFT).is_empty()
+    }
+
+    /// Parses keys like `ctrl+q`, `shift+enter` or `f5`
+    fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim().to_lowercase();
+        let mut parts: Vec<&str> = spec.split('+').collect();
+        // A trailing "+" means the plus key itself, e.g. "ctrl++"
+        let key = if spec.ends_with("++") || spec == "+" {
+            parts.truncate(parts.len().saturating_sub(2));
+            "+"
+        } else {
+            parts.pop().unwrap_or("")
+        };
+
+        let mut modifiers = KeyModifiers::NONE;
+        for part in parts {
+            modifiers |= match part {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" | "meta" | "option" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                other => bail!("Unknown modifier '{}' in key '{}'", other, spec),
+            };
+        }
+
+        let code = match key {
+            "enter" | "return" => KeyCode::Enter,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "esc" | "escape" => KeyCode::Esc,
+            "backspace" => KeyCode::Backspace,
+            "delete" | "del" => KeyCode::Delete,
+            "insert" | "ins" => KeyCode::Insert,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" | "pgup" => KeyCode::PageUp,
+            "pagedown" | "pgdn" => KeyCode::PageDown,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "space" => KeyCode::Char(' '),
+            f if f.len() > 1 && f.starts_with('f') && f[1..].parse::<u8>().is_ok() => {
+                KeyCode::F(f[1..].parse().unwrap_or(1))
+            }
+            c if c.chars().count() == 1 => KeyCode::Char(c.chars().next().unwrap_or(' ')),
+            other => bail!("Unknown key '{}' in '{}'", other, spec),
+        };
+        Ok(KeyBinding::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c.to_uppercase()),
+            KeyCode::F(n) => write!(f, "F{}", n),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+/// Maps key presses to actions
+pub struct Keymap {
+    bindings: Vec<(KeyBinding, Action)>,
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults, replacing the keys of every action named in `overrides`
+    pub fn from_config(overrides: &BTreeMap<String, KeyList>) -> Result<Self> {
+        for name in overrides.keys() {
+            if !Action::ALL.iter().any(|action| action.name() == name) {
+                let names: Vec<&str> = Action::ALL.iter().map(|action| action.name()).collect();
+                bail!("Unknown keymap action '{}' (expected one of: {})", name, names.join(", "));
+            }
+        }
+
+        let mut bindings: Vec<(KeyBinding, Action)> = Vec::new();
+        for action in Action::ALL {
+            let keys = match overrides.get(action.name()) {
+                Some(list) => list.keys(),
+                None => action.default_keys().to_vec(),
+            };
+            for key in keys {
+                let binding = KeyBinding::parse(key)
+                    .map_err(|e| anyhow!("Keymap entry '{}': {}", action.name(), e))?;
+                if binding.types_text() && !action.edits_text() {
+                    bail!(
+                        "Keymap entry '{}': {} types a character, so it cannot be bound to this action; add Ctrl or Alt",
+                        action.name(),
+                        binding
+                    );
+                }
+                if let Some((_, other)) = bindings.iter().find(|(bound, other)| *bound == binding && *other != action) {
+                    bail!("Key {} is bound to both '{}' and '{}'", binding, other.name(), action.name());
+                }
+                bindings.push((binding, action));
+            }
+        }
+        Ok(Keymap { bindings })
+    }
+
+    pub fn action(&self, code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+        let pressed = KeyBinding::new(code, modifiers);
+        self.bindings.iter().find(|(binding, _)| *binding == pressed).map(|(_, action)| *action)
+    }
+
+    /// The first key bound to an action, for help text
+    pub fn key_for(&self, action: Action) -> String {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == action)
+            .map(|(binding, _)| binding.to_string())
+            .unwrap_or_else(|| "(unbound)".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyBinding {
+        KeyBinding::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_modifiers_and_named_keys() {
+        assert_eq!(KeyBinding::parse("ctrl+q").unwrap(), key(KeyCode::Char('q'), KeyModifiers::CONTROL));
+        assert_eq!(KeyBinding::parse(" Control+Alt+Enter ").unwrap(), key(KeyCode::Enter, KeyModifiers::CONTROL | KeyModifiers::ALT));
+        assert_eq!(KeyBinding::parse("shift+enter").unwrap(), key(KeyCode::Enter, KeyModifiers::SHIFT));
+        assert_eq!(KeyBinding::parse("pgdn").unwrap(), key(KeyCode::PageDown, KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("F12").unwrap(), key(KeyCode::F(12), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("space").unwrap(), key(KeyCode::Char(' '), KeyModifiers::NONE));
+    }
+
+    #[test]
+    fn parses_the_plus_key() {
+        assert_eq!(KeyBinding::parse("+").unwrap(), key(KeyCode::Char('+'), KeyModifiers::NONE));
+        assert_eq!(KeyBinding::parse("ctrl++").unwrap(), key(KeyCode::Char('+'), KeyModifiers::CONTROL));
+    }
+
+    #[test]
+    fn shift_is_folded_into_letters_and_tab() {
+        // Terminals report Shift+A as an uppercase letter with or without the Shift flag
+        assert_eq!(key(KeyCode::Char('A'), KeyModifiers::NONE), KeyBinding::parse("shift+a").unwrap());
+        assert_eq!(key(KeyCode::Char('A'), KeyModifiers::SHIFT), KeyBinding::parse("shift+a").unwrap());
+        assert_eq!(key(KeyCode::Char('?'), KeyModifiers::SHIFT), KeyBinding::parse("?").unwrap());
+        assert_eq!(key(KeyCode::Tab, KeyModifiers::SHIFT), KeyBinding::parse("backtab").unwrap());
+        assert_eq!(key(KeyCode::BackTab, KeyModifiers::SHIFT), KeyBinding::parse("shift+tab").unwrap());
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_modifiers() {
+        for spec in ["", "ctrl+", "hyper+q", "ctrl+foo", "fx", "f0x", "qq"] {
+            assert!(KeyBinding::parse(spec).is_err(), "'{}' was accepted", spec);
+        }
+    }
+
+    #[test]
+    fn displays_bindings_for_help_text() {
+        assert_eq!(KeyBinding::parse("ctrl+shift+s").unwrap().to_string(), "Ctrl+Shift+S");
+        assert_eq!(KeyBinding::parse("pageup").unwrap().to_string(), "PgUp");
+        assert_eq!(KeyBinding::parse("shift+tab").unwrap().to_string(), "Shift+Tab");
+    }
+
+    #[test]
+    fn config_overrides_replace_an_actions_keys() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("quit".to_string(), KeyList::Many(vec!["ctrl+x".into(), "f10".into()]));
+        let keymap = Keymap::from_config(&overrides).unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('x'), KeyModifiers::CONTROL), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::F(10), KeyModifiers::NONE), Some(Action::Quit));
+        assert_eq!(keymap.action(KeyCode::Char('q'), KeyModifiers::CONTROL), None);
+
+        overrides.insert("no_such_action".to_string(), KeyList::One("f1".into()));
+        assert!(Keymap::from_config(&overrides).is_err());
+    }
+
+    #[test]
+    fn character_keys_only_go_to_editing_actions() {
+        for key in ["q", "Q", "shift+q", "space", "shift+1"] {
+            let overrides = BTreeMap::from([("quit".to_string(), KeyList::One(key.into()))]);
+            assert!(Keymap::from_config(&overrides).is_err(), "'{}' was accepted for quit", key);
+        }
+        let overrides = BTreeMap::from([
+            ("quit".to_string(), KeyList::One("alt+q".into())),
+            ("undo".to_string(), KeyList::One("u".into())),
+        ]);
+        let keymap = Keymap::from_config(&overrides).unwrap();
+        assert_eq!(keymap.action(KeyCode::Char('u'), KeyModifiers::NONE), Some(Action::Undo));
+    }
+
+    #[test]
+    fn rejects_a_key_bound_to_two_actions() {
+        // Ctrl+R is history_search's default key
+        let overrides = BTreeMap::from([("save_query".to_string(), KeyList::One("ctrl+r".into()))]);
+        let error = Keymap::from_config(&overrides).err().unwrap().to_string();
+        assert!(error.contains("save_query") && error.contains("history_search"), "{}", error);
+
+        let overrides = BTreeMap::from([("quit".to_string(), KeyList::Many(vec!["ctrl+q".into(), "ctrl+q".into()]))]);
+        assert!(Keymap::from_config(&overrides).is_ok());
+    }
+
+    #[test]
+    fn default_keys_pass_the_config_checks() {
+        let keymap = Keymap::from_config(&BTreeMap::new()).unwrap();
+        assert_eq!(keymap.action(KeyCode::Enter, KeyModifiers::NONE), Some(Action::Execute));
+        assert_eq!(keymap.action(KeyCode::Char('q'), KeyModifiers::NONE), None);
+    }
+}
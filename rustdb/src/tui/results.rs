@@ -0,0 +1,188 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Rect},
+    style::{Modifier, Style},
+    text::Span,
+    widgets::{Block, Cell, Row, Table},
+};
+use rusqlite::types::ValueRef;
+
+use crate::output::{RowWriter, value_to_string};
+
+/// Rows kept in memory for the grid; the rest are only counted
+const MAX_GRID_ROWS: usize = 10_000;
+/// Widest a column gets before its cells are cut off
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Scrollable result grid with a row/column cursor
+pub struct ResultGrid {
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    widths: Vec<usize>,
+    total_rows: usize,
+    row: usize,
+    col: usize,
+    row_offset: usize,
+    col_offset: usize,
+    /// Data rows that fit on screen, updated on every draw
+    page_height: usize,
+}
+
+impl ResultGrid {
+    pub fn new() -> Self {
+        ResultGrid {
+            columns: Vec::new(),
+            rows: Vec::new(),
+            widths: Vec::new(),
+            total_rows: 0,
+            row: 0,
+            col: 0,
+            row_offset: 0,
+            col_offset: 0,
+            page_height: 1,
+        }
+    }
+
+    /// Moves the cursor; returns false for keys the grid does not use
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        let last_row = self.rows.len().saturating_sub(1);
+        let last_col = self.columns.len().saturating_sub(1);
+        match code {
+            KeyCode::Up => self.row = self.row.saturating_sub(1),
+            KeyCode::Down => self.row = (self.row + 1).min(last_row),
+            KeyCode::Left => self.col = self.col.saturating_sub(1),
+            KeyCode::Right => self.col = (self.col + 1).min(last_col),
+            KeyCode::PageUp => self.row = self.row.saturating_sub(self.page_height),
+            KeyCode::PageDown => self.row = (self.row + self.page_height).min(last_row),
+            KeyCode::Home => self.row = 0,
+            KeyCode::End => self.row = last_row,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Row count and cursor position for the status line
+    pub fn status(&self) -> String {
+        if self.columns.is_empty() {
+            return String::new();
+        }
+        let mut status = format!("{} row(s)", self.total_rows);
+        if !self.rows.is_empty() {
+            status.push_str(&format!(
+                " | row {}/{} | column {}/{} ({})",
+                self.row + 1,
+                self.rows.len(),
+                self.col + 1,
+                self.columns.len(),
+                self.columns[self.col]
+            ));
+        }
+        if self.total_rows > self.rows.len() {
+            status.push_str(&format!(" | showing first {}", self.rows.len()));
+        }
+        status
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, block: Block) {
+        let inner = block.inner(area);
+        // One line goes to the header
+        self.page_height = (inner.height as usize).saturating_sub(1).max(1);
+        self.scroll_into_view(inner.width as usize);
+
+        let visible = self.visible_columns(inner.width as usize);
+        let header = Row::new(visible.clone().map(|i| Span::styled(
+            self.columns[i].as_str(),
+            Style::default().add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        )));
+
+        let end = (self.row_offset + self.page_height).min(self.rows.len());
+        let rows = (self.row_offset..end).map(|r| {
+            let cells = visible.clone().map(|i| {
+                let cell = Cell::from(truncate(&self.rows[r][i], self.widths[i]));
+                if r == self.row && i == self.col {
+                    cell.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    cell
+                }
+            });
+            let row = Row::new(cells);
+            if r == self.row {
+                row.style(Style::default().add_modifier(Modifier::BOLD))
+            } else {
+                row
+            }
+        });
+
+        let widths: Vec<Constraint> = visible.clone().map(|i| Constraint::Length(self.widths[i] as u16)).collect();
+        let table = Table::new(rows, widths).header(header).column_spacing(1).block(block);
+        f.render_widget(table, area);
+    }
+
+    /// Adjusts the offsets so the cursor cell is on screen
+    fn scroll_into_view(&mut self, width: usize) {
+        if self.row < self.row_offset {
+            self.row_offset = self.row;
+        } else if self.row >= self.row_offset + self.page_height {
+            self.row_offset = self.row + 1 - self.page_height;
+        }
+
+        if self.col < self.col_offset {
+            self.col_offset = self.col;
+        }
+        while self.col_offset < self.col && !self.visible_columns(width).any(|i| i == self.col) {
+            self.col_offset += 1;
+        }
+    }
+
+    /// Columns from the horizontal offset that fit in `width`; always at least one
+    fn visible_columns(&self, width: usize) -> std::ops::Range<usize> {
+        let mut used = 0;
+        let mut end = self.col_offset;
+        while end < self.columns.len() {
+            used += self.widths[end] + if end > self.col_offset { 1 } else { 0 };
+            if used > width && end > self.col_offset {
+                break;
+            }
+            end += 1;
+        }
+        self.col_offset..end
+    }
+}
+
+impl RowWriter for ResultGrid {
+    fn begin(&mut self, columns: &[String]) -> Result<()> {
+        *self = ResultGrid::new();
+        self.columns = columns.to_vec();
+        self.widths = columns.iter().map(|c| c.chars().count().min(MAX_COLUMN_WIDTH)).collect();
+        Ok(())
+    }
+
+    fn write_row(&mut self, row: &[ValueRef]) -> Result<()> {
+        if self.rows.len() < MAX_GRID_ROWS {
+            // Cells are shown on one line
+            let cells: Vec<String> = row.iter().map(|v| value_to_string(v).replace(['\n', '\r', '\t'], " ")).collect();
+            for (width, cell) in self.widths.iter_mut().zip(&cells) {
+                *width = (*width).max(cell.chars().count().min(MAX_COLUMN_WIDTH));
+            }
+            self.rows.push(cells);
+        }
+        self.total_rows += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Cuts a cell to the column width, marking the cut with an ellipsis
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
@@ -0,0 +1,278 @@
+use crossterm::event::KeyCode;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+use crate::history::local_time;
+use crate::params::parse_param_spec;
+use crate::saved_queries::{QueryChanges, SavedQuery, SavedStores, ScopedQuery, parse_tags};
+
+/// Text being typed for an action on the selected query
+enum Prompt {
+    Rename,
+    Description,
+    Tags,
+    Params,
+    ConfirmDelete,
+}
+
+impl Prompt {
+    fn label(&self) -> &'static str {
+        match self {
+            Prompt::Rename => "New name",
+            Prompt::Description => "Description",
+            Prompt::Tags => "Tags (comma-separated)",
+            Prompt::Params => "Parameters (name:type=default, comma-separated)",
+            Prompt::ConfirmDelete => "Delete this query? (y/n)",
+        }
+    }
+}
+
+/// How the saved query screen dealt with a key
+pub enum SavedKey {
+    Handled,
+    /// Close the screen, showing this message
+    Close(String),
+    /// Load this query into the editor
+    Load(SavedQuery),
+}
+
+/// The F2 screen: browse, load, update, rename, describe, tag, declare parameters of and delete saved queries
+pub struct SavedScreen {
+    stores: SavedStores,
+    /// Queries from every store, labelled by scope
+    queries: Vec<ScopedQuery>,
+    selected: usize,
+    prompt: Option<(Prompt, String)>,
+    /// Result of the last action
+    message: Option<String>,
+}
+
+impl SavedScreen {
+    /// Opens the screen, or returns `None` when nothing is saved
+    pub fn open(stores: SavedStores) -> anyhow::Result<Option<Self>> {
+        let queries = stores.load()?;
+        if queries.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(SavedScreen { stores, queries, selected: 0, prompt: None, message: None }))
+    }
+
+    /// `editor_sql` is what an update replaces the selected query's SQL with
+    pub fn handle_key(&mut self, code: KeyCode, editor_sql: &str) -> SavedKey {
+        // The list is reloaded after every change, which can leave it empty, e.g. if the file was removed meanwhile
+        if !self.queries.is_empty() {
+            let key = self.handle(code, editor_sql);
+            if !self.queries.is_empty() {
+                return key;
+            }
+        }
+        let message = match self.message.take() {
+            Some(m) if m.ends_with('.') => format!("{} ", m),
+            Some(m) => format!("{}. ", m),
+            None => String::new(),
+        };
+        SavedKey::Close(format!("{}No saved queries left.", message))
+    }
+
+    fn handle(&mut self, code: KeyCode, editor_sql: &str) -> SavedKey {
+        if let Some((prompt, input)) = &mut self.prompt {
+            match (prompt, code) {
+                (Prompt::ConfirmDelete, KeyCode::Char('y' | 'Y')) => {
+                    let ScopedQuery { scope, query, .. } = &self.queries[self.selected];
+                    let (name, scope) = (query.name.clone(), *scope);
+                    self.prompt = None;
+                    let result = self.stores.delete(&name, Some(scope));
+                    self.apply(result, format!("Deleted '{}' from the {} queries.", name, scope.name()));
+                }
+                (Prompt::ConfirmDelete, _) => {
+                    self.prompt = None;
+                    self.message = Some("Delete cancelled.".into());
+                }
+                (_, KeyCode::Char(c)) => input.push(c),
+                (_, KeyCode::Backspace) => {
+                    input.pop();
+                }
+                (_, KeyCode::Esc) => self.prompt = None,
+                (_, KeyCode::Enter) => {
+                    if let Some((prompt, input)) = self.prompt.take() {
+                        self.submit(prompt, input);
+                    }
+                }
+                _ => {}
+            }
+            return SavedKey::Handled;
+        }
+
+        let ScopedQuery { scope, query, .. } = &self.queries[self.selected];
+        let scope = *scope;
+        match code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(self.queries.len() - 1),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = self.queries.len() - 1,
+            KeyCode::Enter => return SavedKey::Load(query.clone()),
+            KeyCode::Esc => return SavedKey::Close("Closed saved queries.".into()),
+            KeyCode::Char('r') => self.prompt = Some((Prompt::Rename, query.name.clone())),
+            KeyCode::Char('e') => self.prompt = Some((Prompt::Description, query.description.clone().unwrap_or_default())),
+            KeyCode::Char('t') => self.prompt = Some((Prompt::Tags, query.tags.join(", "))),
+            KeyCode::Char('p') if query.params.is_empty() => {
+                self.message = Some("This query has no :name placeholders to declare.".into());
+            }
+            KeyCode::Char('p') => {
+                let params: Vec<String> = query.params.iter().map(|p| p.to_string()).collect();
+                self.prompt = Some((Prompt::Params, params.join(", ")));
+            }
+            KeyCode::Char('d') => self.prompt = Some((Prompt::ConfirmDelete, String::new())),
+            KeyCode::Char('u') if editor_sql.trim().is_empty() => {
+                self.message = Some("The editor is empty; write the new SQL there first.".into());
+            }
+            KeyCode::Char('u') => {
+                let name = query.name.clone();
+                let changes = QueryChanges { sql: Some(editor_sql.to_string()), ..QueryChanges::default() };
+                let result = self.stores.update(&name, Some(scope), changes);
+                self.apply(result, format!("Replaced the SQL of '{}' with the editor's.", name));
+            }
+            _ => {}
+        }
+        SavedKey::Handled
+    }
+
+    /// Pasted text goes into an open prompt
+    pub fn paste(&mut self, text: &str) {
+        if let Some((prompt, input)) = &mut self.prompt
+            && !matches!(prompt, Prompt::ConfirmDelete)
+        {
+            input.push_str(&text.replace(['\r', '\n'], " "));
+        }
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, block: Block) {
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(2)])
+            .split(inner);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(rows[0]);
+
+        let muted = Style::default().fg(Color::DarkGray);
+        let items: Vec<ListItem> = self
+            .queries
+            .iter()
+            .map(|q| {
+                // Hidden by a same-named query in a more specific scope
+                let name = if q.shadowed { Span::styled(q.query.name.as_str(), muted) } else { Span::raw(q.query.name.as_str()) };
+                ListItem::new(Line::from(vec![name, Span::styled(format!(" {}", q.scope.name()), muted)]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::RIGHT))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        f.render_stateful_widget(list, columns[0], &mut state);
+
+        if let Some(selected) = self.queries.get(self.selected) {
+            let details = self.details(selected, muted);
+            f.render_widget(Paragraph::new(Text::from(details)), columns[1].inner(&Margin::new(1, 0)));
+        }
+
+        let status = match &self.prompt {
+            Some((prompt, input)) => Line::from(format!("{}: {}", prompt.label(), input)),
+            None => Line::from(self.message.clone().unwrap_or_default()),
+        };
+        let hint = Line::styled(
+            "Enter load  u update SQL from editor  r rename  e describe  t tag  p params  d delete  Esc close",
+            muted,
+        );
+        f.render_widget(Paragraph::new(Text::from(vec![status, hint])), rows[1]);
+    }
+
+    /// Name, scope, description, tags, parameters, times and SQL of a query
+    fn details(&self, selected: &ScopedQuery, muted: Style) -> Vec<Line<'static>> {
+        let ScopedQuery { scope, query, shadowed } = selected;
+        let field = |label: &'static str, value: String| Line::from(vec![Span::styled(label, muted), Span::raw(value)]);
+        let mut details = vec![field("Name: ", query.name.clone())];
+        let mut location = scope.name().to_string();
+        if let Ok(store) = self.stores.store(*scope) {
+            location.push_str(&format!(" ({})", store.path.display()));
+        }
+        if *shadowed {
+            location.push_str(", shadowed");
+        }
+        details.push(field("Scope: ", location));
+        if let Some(description) = &query.description {
+            details.push(field("Description: ", description.clone()));
+        }
+        if !query.tags.is_empty() {
+            details.push(field("Tags: ", query.tags.join(", ")));
+        }
+        if !query.params.is_empty() {
+            let params: Vec<String> = query.params.iter().map(|p| p.to_string()).collect();
+            details.push(field("Parameters: ", params.join(", ")));
+        }
+        if let Some(created) = &query.created {
+            details.push(field("Created: ", local_time(created)));
+        }
+        if let Some(updated) = &query.updated {
+            details.push(field("Updated: ", local_time(updated)));
+        }
+        details.push(Line::from(""));
+        details.extend(query.sql.lines().map(|line| Line::from(line.to_string())));
+        details
+    }
+
+    fn submit(&mut self, prompt: Prompt, input: String) {
+        let ScopedQuery { scope, query, .. } = &self.queries[self.selected];
+        let (name, scope) = (query.name.clone(), *scope);
+        let update = |changes| self.stores.update(&name, Some(scope), changes);
+        match prompt {
+            Prompt::Rename => {
+                let result = self.stores.rename(&name, Some(scope), &input);
+                self.apply(result, format!("Renamed '{}' to '{}'.", name, input.trim()));
+                // Follow the query to its new place in the list
+                if let Some(i) = self.queries.iter().position(|q| q.scope == scope && q.query.name == input.trim()) {
+                    self.selected = i;
+                }
+            }
+            Prompt::Description => {
+                let result = update(QueryChanges { description: Some(input), ..QueryChanges::default() });
+                self.apply(result, format!("Updated the description of '{}'.", name));
+            }
+            Prompt::Tags => {
+                let result = update(QueryChanges { tags: Some(parse_tags(&input)), ..QueryChanges::default() });
+                self.apply(result, format!("Updated the tags of '{}'.", name));
+            }
+            Prompt::Params => {
+                let result = input
+                    .split(',')
+                    .filter(|spec| !spec.trim().is_empty())
+                    .map(parse_param_spec)
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .and_then(|params| update(QueryChanges { params: Some(params), ..QueryChanges::default() }));
+                self.apply(result, format!("Updated the parameters of '{}'.", name));
+            }
+            Prompt::ConfirmDelete => {}
+        }
+    }
+
+    /// Reports the outcome of a change and reloads the list
+    fn apply<T>(&mut self, result: anyhow::Result<T>, success: String) {
+        self.message = Some(match result {
+            Ok(_) => success,
+            Err(e) => format!("Error: {e:#}"),
+        });
+        match self.stores.load() {
+            Ok(queries) => self.queries = queries,
+            Err(e) => self.message = Some(format!("Error: {e:#}")),
+        }
+        self.selected = self.selected.min(self.queries.len().saturating_sub(1));
+    }
+}
@@ -0,0 +1,324 @@
+use anyhow::Result;
+use crossterm::event::KeyCode;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+};
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// Object types in the order they are listed
+const GROUPS: [(&str, &str); 4] = [("table", "Tables"), ("view", "Views"), ("index", "Indexes"), ("trigger", "Triggers")];
+
+/// A table, view, index or trigger from `sqlite_schema`
+struct SchemaObject {
+    name: String,
+    kind: &'static str,
+    /// The table an index or trigger belongs to
+    table: String,
+    sql: Option<String>,
+    /// Column names and declared types; indexes list the columns they cover
+    columns: Vec<(String, String)>,
+}
+
+/// One line of the tree
+#[derive(Clone, Copy)]
+enum Row {
+    Group(usize),
+    Object(usize),
+    Column(usize, usize),
+}
+
+/// Quick actions offered for the selected object
+#[derive(Clone, Copy)]
+enum QuickAction {
+    Preview,
+    Analyze,
+    ShowCreate,
+    InsertName,
+}
+
+impl QuickAction {
+    fn label(self) -> &'static str {
+        match self {
+            QuickAction::Preview => "Preview first 100 rows",
+            QuickAction::Analyze => "Analyze",
+            QuickAction::ShowCreate => "Show CREATE statement",
+            QuickAction::InsertName => "Insert name",
+        }
+    }
+
+    fn key(self) -> char {
+        match self {
+            QuickAction::Preview => 'p',
+            QuickAction::Analyze => 'a',
+            QuickAction::ShowCreate => 'c',
+            QuickAction::InsertName => 'i',
+        }
+    }
+}
+
+/// What the TUI should do for a chosen quick action
+pub enum SidebarAction {
+    /// Run a query showing the first rows of this table or view
+    Preview(String),
+    Analyze(String),
+    /// Show this CREATE statement
+    ShowCreate(String),
+    /// Insert this name at the editor's cursor
+    InsertName(String),
+}
+
+/// How the sidebar dealt with a key
+pub enum SidebarKey {
+    Ignored,
+    Handled,
+    Action(SidebarAction),
+}
+
+/// Tree of the database's tables, views, indexes and triggers
+pub struct Sidebar {
+    objects: Vec<SchemaObject>,
+    /// Names of expanded objects, kept when the schema is reloaded
+    expanded: HashSet<String>,
+    selected: usize,
+    offset: usize,
+    /// Selected entry while the quick action menu is open
+    menu: Option<usize>,
+}
+
+impl Sidebar {
+    pub fn load(conn: &Connection) -> Result<Self> {
+        let mut sidebar = Sidebar {
+            objects: Vec::new(),
+            expanded: HashSet::new(),
+            selected: 0,
+            offset: 0,
+            menu: None,
+        };
+        sidebar.reload(conn)?;
+        Ok(sidebar)
+    }
+
+    /// Reads the schema again, keeping expanded objects and the selection where possible
+    pub fn reload(&mut self, conn: &Connection) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "SELECT type, name, tbl_name, sql FROM sqlite_schema
+             WHERE type IN ('table', 'view', 'index', 'trigger') AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )?;
+        let found = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get(3)?))
+            })?
+            .collect::<rusqlite::Result<Vec<(String, String, String, Option<String>)>>>()?;
+
+        let mut column_stmt = conn.prepare("SELECT name, type FROM pragma_table_info(?1)")?;
+        let mut index_stmt = conn.prepare("SELECT name FROM pragma_index_info(?1) ORDER BY seqno")?;
+        let mut objects = Vec::new();
+        for (kind, _) in GROUPS {
+            for (_, name, table, sql) in found.iter().filter(|(k, ..)| k == kind) {
+                let columns = match kind {
+                    "table" | "view" => column_stmt
+                        .query_map([name], |row| Ok((row.get(0)?, row.get(1)?)))?
+                        .collect::<rusqlite::Result<Vec<(String, String)>>>()?,
+                    "index" => index_stmt
+                        .query_map([name], |row| {
+                            // Expression indexes have no column name
+                            let column = row.get::<_, Option<String>>(0)?.unwrap_or_else(|| "<expression>".to_string());
+                            Ok((column, String::new()))
+                        })?
+                        .collect::<rusqlite::Result<Vec<(String, String)>>>()?,
+                    _ => Vec::new(),
+                };
+                objects.push(SchemaObject { name: name.clone(), kind, table: table.clone(), sql: sql.clone(), columns });
+            }
+        }
+        self.objects = objects;
+        self.selected = self.selected.min(self.rows().len().saturating_sub(1));
+        self.menu = None;
+        Ok(())
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode) -> SidebarKey {
+        if let Some(selected) = self.menu {
+            let actions = self.actions();
+            match code {
+                KeyCode::Up => self.menu = Some((selected + actions.len() - 1) % actions.len()),
+                KeyCode::Down => self.menu = Some((selected + 1) % actions.len()),
+                KeyCode::Enter => return self.choose(actions[selected]),
+                KeyCode::Char(c) => {
+                    if let Some(action) = actions.iter().find(|action| action.key() == c) {
+                        return self.choose(*action);
+                    }
+                }
+                KeyCode::Esc => self.menu = None,
+                _ => {}
+            }
+            return SidebarKey::Handled;
+        }
+
+        let rows = self.rows();
+        let last = rows.len().saturating_sub(1);
+        match code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(10),
+            KeyCode::PageDown => self.selected = (self.selected + 10).min(last),
+            KeyCode::Home => self.selected = 0,
+            KeyCode::End => self.selected = last,
+            KeyCode::Right => {
+                if let Some(Row::Object(i)) = rows.get(self.selected) {
+                    self.expanded.insert(self.objects[*i].name.clone());
+                }
+            }
+            KeyCode::Left => match rows.get(self.selected) {
+                Some(Row::Object(i)) => {
+                    self.expanded.remove(&self.objects[*i].name);
+                }
+                // From a column, collapse its object and select that
+                Some(Row::Column(i, _)) => {
+                    let i = *i;
+                    self.expanded.remove(&self.objects[i].name);
+                    self.selected = self.rows().iter().position(|row| matches!(row, Row::Object(o) if *o == i)).unwrap_or(0);
+                }
+                _ => {}
+            },
+            KeyCode::Enter => match rows.get(self.selected) {
+                Some(Row::Object(_)) => self.menu = Some(0),
+                Some(Row::Column(i, c)) if self.objects[*i].kind != "index" => {
+                    return SidebarKey::Action(SidebarAction::InsertName(self.objects[*i].columns[*c].0.clone()));
+                }
+                _ => {}
+            },
+            _ => return SidebarKey::Ignored,
+        }
+        SidebarKey::Handled
+    }
+
+    pub fn render(&mut self, f: &mut Frame, area: Rect, block: Block) {
+        let inner = block.inner(area);
+        let rows = self.rows();
+        let muted = Style::default().fg(Color::DarkGray);
+        let items: Vec<ListItem> = rows
+            .iter()
+            .map(|row| match *row {
+                Row::Group(g) => {
+                    let (kind, title) = GROUPS[g];
+                    let count = self.objects.iter().filter(|o| o.kind == kind).count();
+                    ListItem::new(Line::from(vec![
+                        Span::styled(title, Style::default().add_modifier(Modifier::BOLD)),
+                        Span::styled(format!(" ({})", count), muted),
+                    ]))
+                }
+                Row::Object(i) => {
+                    let object = &self.objects[i];
+                    let marker = match (object.columns.is_empty(), self.expanded.contains(&object.name)) {
+                        (true, _) => "  ",
+                        (false, true) => "▾ ",
+                        (false, false) => "▸ ",
+                    };
+                    let mut spans = vec![Span::raw(format!(" {}{}", marker, object.name))];
+                    if matches!(object.kind, "index" | "trigger") {
+                        spans.push(Span::styled(format!(" on {}", object.table), muted));
+                    }
+                    ListItem::new(Line::from(spans))
+                }
+                Row::Column(i, c) => {
+                    let (name, col_type) = &self.objects[i].columns[c];
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("     {} ", name)),
+                        Span::styled(col_type.clone(), muted),
+                    ]))
+                }
+            })
+            .collect();
+
+        let list = List::new(items).block(block).highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected)).with_offset(self.offset);
+        f.render_stateful_widget(list, area, &mut state);
+        self.offset = state.offset();
+
+        if let Some(selected) = self.menu {
+            let row = inner.y + self.selected.saturating_sub(self.offset) as u16;
+            self.render_menu(f, selected, (inner.x + 2, row));
+        }
+    }
+
+    /// The quick action menu, under the selected row or above it when there is no room
+    fn render_menu(&self, f: &mut Frame, selected: usize, anchor: (u16, u16)) {
+        let actions = self.actions();
+        let screen = f.size();
+        let width = (actions.iter().map(|a| a.label().len()).max().unwrap_or(0) as u16 + 8).min(screen.width);
+        let height = (actions.len() as u16 + 2).min(screen.height);
+        let x = anchor.0.min(screen.width.saturating_sub(width));
+        let below = anchor.1 + 1;
+        let y = if below + height <= screen.height { below } else { anchor.1.saturating_sub(height) };
+        let area = Rect::new(x, y, width, height);
+
+        let items: Vec<ListItem> = actions
+            .iter()
+            .map(|action| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!(" {} ", action.key()), Style::default().fg(Color::Yellow)),
+                    Span::raw(action.label()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(selected));
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Group headings, objects, and the columns of expanded objects
+    fn rows(&self) -> Vec<Row> {
+        let mut rows = Vec::new();
+        for (g, (kind, _)) in GROUPS.iter().enumerate() {
+            rows.push(Row::Group(g));
+            for (i, object) in self.objects.iter().enumerate().filter(|(_, o)| o.kind == *kind) {
+                rows.push(Row::Object(i));
+                if self.expanded.contains(&object.name) {
+                    rows.extend((0..object.columns.len()).map(|c| Row::Column(i, c)));
+                }
+            }
+        }
+        rows
+    }
+
+    fn selected_object(&self) -> Option<&SchemaObject> {
+        match self.rows().get(self.selected) {
+            Some(Row::Object(i)) => Some(&self.objects[*i]),
+            _ => None,
+        }
+    }
+
+    /// Only tables and views have rows to preview or analyze
+    fn actions(&self) -> Vec<QuickAction> {
+        match self.selected_object() {
+            Some(object) if matches!(object.kind, "table" | "view") => {
+                vec![QuickAction::Preview, QuickAction::Analyze, QuickAction::ShowCreate, QuickAction::InsertName]
+            }
+            _ => vec![QuickAction::ShowCreate, QuickAction::InsertName],
+        }
+    }
+
+    fn choose(&mut self, action: QuickAction) -> SidebarKey {
+        self.menu = None;
+        let Some(object) = self.selected_object() else {
+            return SidebarKey::Handled;
+        };
+        let name = object.name.clone();
+        SidebarKey::Action(match action {
+            QuickAction::Preview => SidebarAction::Preview(name),
+            QuickAction::Analyze => SidebarAction::Analyze(name),
+            QuickAction::ShowCreate => SidebarAction::ShowCreate(object.sql.clone().unwrap_or_default()),
+            QuickAction::InsertName => SidebarAction::InsertName(name),
+        })
+    }
+}
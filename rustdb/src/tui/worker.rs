@@ -0,0 +1,166 @@
+use anyhow::{Result, anyhow};
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, InterruptHandle};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::results::ResultGrid;
+use crate::analyze::table_report;
+use crate::output::{RowWriter, write_rows};
+use crate::params::{ParamBinder, QueryParams};
+use crate::query::is_dml;
+use crate::script::split_statements;
+
+/// Most frequent values listed per column in reports, as `analyze` does by default
+const REPORT_TOP_VALUES: usize = 5;
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// What running a statement from the editor produced
+pub enum RunOutcome {
+    /// Rows were loaded into the grid
+    Rows(usize, ResultGrid),
+    /// Rows changed by INSERT, UPDATE or DELETE
+    Changed(usize),
+    /// Any other statement, e.g. CREATE TABLE
+    Done,
+    /// A text report, such as a table analysis
+    Report(String),
+}
+
+/// A statement running on a background thread so the TUI keeps drawing and reading keys
+pub struct RunningQuery {
+    /// The statement, for the history; `None` for reports
+    pub sql: Option<String>,
+    started: Instant,
+    /// Rows fetched so far, counted by the worker
+    fetched: Arc<AtomicUsize>,
+    interrupt: InterruptHandle,
+    cancelled: bool,
+    /// The outcome and how long the statement took, sent when the worker is done
+    result: Receiver<(Result<RunOutcome>, Duration)>,
+}
+
+impl RunningQuery {
+    /// Starts running `sql` with `params` bound; the worker holds the connection lock until it is done
+    pub fn start(conn: &Arc<Mutex<Connection>>, sql: String, params: QueryParams) -> Self {
+        let statement = sql.clone();
+        Self::spawn(conn, Some(sql), move |conn, counter| run_statement(conn, &statement, &params, counter))
+    }
+
+    /// Starts building the analyze report for a table or view
+    pub fn analyze(conn: &Arc<Mutex<Connection>>, table: String) -> Self {
+        Self::spawn(conn, None, move |conn, _| Ok(RunOutcome::Report(table_report(conn, &table, REPORT_TOP_VALUES)?)))
+    }
+
+    fn spawn<F>(conn: &Arc<Mutex<Connection>>, sql: Option<String>, job: F) -> Self
+    where
+        F: FnOnce(&Connection, RowCounter) -> Result<RunOutcome> + Send + 'static,
+    {
+        let interrupt = lock(conn).get_interrupt_handle();
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let (sender, result) = mpsc::channel();
+
+        let conn = Arc::clone(conn);
+        let counter = RowCounter(Arc::clone(&fetched));
+        thread::spawn(move || {
+            let started = Instant::now();
+            let outcome = job(&lock(&conn), counter);
+            // The TUI may have quit in the meantime, leaving nobody to receive this
+            let _ = sender.send((outcome, started.elapsed()));
+        });
+
+        RunningQuery { sql, started: Instant::now(), fetched, interrupt, cancelled: false, result }
+    }
+
+    /// The result and time taken once the worker has finished
+    pub fn poll(&self) -> Option<(Result<RunOutcome>, Duration)> {
+        match self.result.try_recv() {
+            Ok(finished) => Some(finished),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some((Err(anyhow!("Query worker stopped unexpectedly")), self.elapsed()))
+            }
+        }
+    }
+
+    /// Asks SQLite to abandon the statement; the worker then finishes with an "interrupted" error
+    pub fn cancel(&mut self) {
+        self.interrupt.interrupt();
+        self.cancelled = true;
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Spinner, elapsed time and rows fetched, for the status line
+    pub fn status(&self, cancel_key: &str) -> String {
+        let elapsed = self.elapsed();
+        let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+        let fetched = self.fetched.load(Ordering::Relaxed);
+        if self.cancelled {
+            format!("{} Cancelling… {:.1}s", frame, elapsed.as_secs_f64())
+        } else {
+            format!(
+                "{} Running… {:.1}s | {} row(s) fetched | {} to cancel",
+                frame,
+                elapsed.as_secs_f64(),
+                fetched,
+                cancel_key
+            )
+        }
+    }
+}
+
+/// Locks the shared connection, carrying on if a worker panicked while holding it
+pub fn lock(conn: &Mutex<Connection>) -> std::sync::MutexGuard<'_, Connection> {
+    conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs the statements in `sql` in order, loading any rows into a fresh grid; the outcome is the last statement's
+fn run_statement(conn: &Connection, sql: &str, params: &QueryParams, mut counter: RowCounter) -> Result<RunOutcome> {
+    let statements = split_statements(sql);
+    let mut binder = ParamBinder::new(params, statements.len());
+    let mut outcome = RunOutcome::Done;
+    for statement in &statements {
+        let mut stmt = conn.prepare(&statement.sql)?;
+        binder.bind(&mut stmt)?;
+        outcome = if stmt.column_count() == 0 {
+            let changed = stmt.raw_execute()?;
+            if is_dml(&statement.sql) { RunOutcome::Changed(changed) } else { RunOutcome::Done }
+        } else {
+            let mut grid = ResultGrid::new();
+            let mut writers: [&mut dyn RowWriter; 2] = [&mut grid, &mut counter];
+            let count = write_rows(&mut stmt, &mut writers)?;
+            RunOutcome::Rows(count, grid)
+        };
+    }
+    binder.finish()?;
+    Ok(outcome)
+}
+
+/// Publishes the number of rows fetched so far to the TUI thread
+struct RowCounter(Arc<AtomicUsize>);
+
+impl RowWriter for RowCounter {
+    fn begin(&mut self, _columns: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn write_row(&mut self, _row: &[ValueRef]) -> Result<()> {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}